@@ -13,23 +13,69 @@ pub const BLUE: &str = "\x1B[34m";
 pub const MAGENTA: &str = "\x1B[35m";
 /// Cyan color
 pub const CYAN: &str = "\x1B[36m";
+/// Orange color, from the 256-color palette
+pub const ORANGE: &str = "\x1B[38;5;208m";
 /// White background
 pub const WHITE_BG: &str = "\x1B[47m";
 /// Reset color
 pub const RESET: &str = "\x1B[0m";
 
+/// Does the terminal support 24-bit colors, according to `COLORTERM`.
+pub fn truecolor_supported() -> bool {
+    matches!(
+        std::env::var("COLORTERM").as_deref(),
+        Ok("truecolor") | Ok("24bit")
+    )
+}
+
+/// Foreground color escape sequence for 24-bit color.
+pub fn fg_rgb((r, g, b): (u8, u8, u8)) -> String {
+    format!("\x1B[38;2;{};{};{}m", r, g, b)
+}
+
+/// Color `index` of `count` distinct colors with evenly spaced hues.
+pub fn palette_rgb(index: usize, count: usize) -> (u8, u8, u8) {
+    let hue = index as f64 * 6.0 / count as f64;
+    let (saturation, value) = (0.7, 0.95);
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let to_u8 = |c: f64| ((c + m) * 255.0).round() as u8;
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
 // https://en.wikipedia.org/wiki/ANSI_escape_code#CSIsection
 pub fn strip_csi(s: &str) -> String {
-    static REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("\x1b\\[[0-9]+[a-zA-Z]").unwrap());
+    static REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("\x1b\\[[0-9;]*[a-zA-Z]").unwrap());
     REGEX.replace_all(s, "").into_owned()
 }
 
 #[cfg(test)]
 mod test {
+    use crate::ansi::fg_rgb;
+    use crate::ansi::palette_rgb;
     use crate::ansi::strip_csi;
 
     #[test]
     fn test() {
         assert_eq!("A\nB\n", strip_csi("\x1B[32mA\x1B[0m\n\x1B[31mB\x1B[0m\n"));
+        assert_eq!("C", strip_csi(&format!("{}C\x1B[0m", fg_rgb((1, 2, 3)))));
+    }
+
+    #[test]
+    fn palette() {
+        assert_eq!((242, 73, 73), palette_rgb(0, 5));
+        let colors: Vec<_> = (0..5).map(|i| palette_rgb(i, 5)).collect();
+        for (i, a) in colors.iter().enumerate() {
+            assert!(colors[i + 1..].iter().all(|b| a != b));
+        }
     }
 }
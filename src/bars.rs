@@ -185,11 +185,11 @@ pub fn plot_halves_u64(values: &[u64], max: u64, highlight: &PlotHighlight) -> S
 #[cfg(test)]
 mod test {
     use crate::bars::_plot;
+    use crate::bars::_plot_halves;
+    use crate::bars::PlotHighlight;
     use crate::bars::f64_to_bucket;
     use crate::bars::plot_halves_u64;
     use crate::bars::plot_u64;
-    use crate::bars::PlotHighlight;
-    use crate::bars::_plot_halves;
 
     #[test]
     fn test_f64_to_range() {
@@ -0,0 +1,98 @@
+//! Capture output of a script, keeping only the last lines.
+
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::process::Child;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::thread::JoinHandle;
+
+/// Threads reading piped stdout and stderr of a child process.
+pub struct Capture {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Capture {
+    /// Start reading output of the child, `None` if its output is not piped.
+    pub fn start(child: &mut Child, max_lines: usize) -> Option<Capture> {
+        let lines = Arc::new(Mutex::new(VecDeque::new()));
+        let mut threads = Vec::new();
+        let outputs: Vec<Box<dyn Read + Send>> = vec![
+            child
+                .stdout
+                .take()
+                .map(|o| Box::new(o) as Box<dyn Read + Send>),
+            child
+                .stderr
+                .take()
+                .map(|o| Box::new(o) as Box<dyn Read + Send>),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if outputs.is_empty() {
+            return None;
+        }
+        for output in outputs {
+            let lines = lines.clone();
+            threads.push(thread::spawn(move || {
+                for line in BufReader::new(output).split(b'\n') {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    };
+                    let mut lines = lines.lock().unwrap();
+                    lines.push_back(String::from_utf8_lossy(&line).into_owned());
+                    if lines.len() > max_lines {
+                        lines.pop_front();
+                    }
+                }
+            }));
+        }
+        Some(Capture { lines, threads })
+    }
+
+    /// Wait until the output is closed and return the last lines.
+    pub fn finish(self) -> Vec<String> {
+        for thread in self.threads {
+            thread.join().unwrap();
+        }
+        self.lines.lock().unwrap().drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::capture::Capture;
+    use crate::sh::SpawnOptions;
+    use crate::sh::spawn_sh;
+
+    #[test]
+    #[cfg(unix)]
+    fn last_lines() {
+        let options = SpawnOptions {
+            capture: true,
+            ..SpawnOptions::default()
+        };
+        let mut child = spawn_sh("for i in 1 2 3 4; do echo $i; done", &options).unwrap();
+        let capture = Capture::start(&mut child, 2).unwrap();
+        child.wait().unwrap();
+        assert_eq!(vec!["3", "4"], capture.finish());
+
+        // Order of stdout and stderr lines depends on the reader threads.
+        let mut child = spawn_sh("echo 1; echo 2 >&2", &options).unwrap();
+        let capture = Capture::start(&mut child, 2).unwrap();
+        child.wait().unwrap();
+        let mut lines = capture.finish();
+        lines.sort();
+        assert_eq!(vec!["1", "2"], lines);
+
+        let mut child = spawn_sh("true", &SpawnOptions::default()).unwrap();
+        assert!(Capture::start(&mut child, 2).is_none());
+        child.wait().unwrap();
+    }
+}
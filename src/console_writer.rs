@@ -1,29 +1,67 @@
 use std::fmt;
+use std::io;
+use std::process;
 
 use crate::ansi::strip_csi;
 
+/// Exit code when stdout or stderr is closed by the reader, as if killed by `SIGPIPE`.
+pub const BROKEN_PIPE_EXIT_CODE: i32 = 141;
+
+/// Write to stdout or stderr, and exit quietly if the reader is gone
+/// (e.g. `absh ... | head`). `SIGPIPE` stays ignored so writes to
+/// `--progress-fd` after the wrapper exits do not kill the benchmark.
+pub fn write_or_exit(out: &mut dyn io::Write, s: &str) -> io::Result<()> {
+    match out.write_all(s.as_bytes()).and_then(|()| out.flush()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => process::exit(BROKEN_PIPE_EXIT_CODE),
+        r => r,
+    }
+}
+
 #[derive(Debug)]
 pub struct ConsoleWriter {
     color: bool,
 }
 
+/// When to use colors on stderr.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// If stderr is a terminal and `NO_COLOR` is not set.
+    Auto,
+    Always,
+    Never,
+}
+
 fn is_tty() -> bool {
     !cfg!(windows) && atty::is(atty::Stream::Stderr)
 }
 
+/// https://no-color.org/
+fn no_color_env() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
 impl ConsoleWriter {
+    pub fn new(choice: ColorChoice) -> ConsoleWriter {
+        let color = match choice {
+            ColorChoice::Auto => is_tty() && !no_color_env(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        ConsoleWriter { color }
+    }
+
     pub fn auto() -> ConsoleWriter {
-        ConsoleWriter { color: is_tty() }
+        ConsoleWriter::new(ColorChoice::Auto)
     }
 }
 
 impl fmt::Write for ConsoleWriter {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        if self.color {
-            eprint!("{}", s);
+        let r = if self.color {
+            write_or_exit(&mut io::stderr(), s)
         } else {
-            eprint!("{}", strip_csi(s));
-        }
-        Ok(())
+            write_or_exit(&mut io::stderr(), &strip_csi(s))
+        };
+        r.map_err(|_| fmt::Error)
     }
 }
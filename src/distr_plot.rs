@@ -2,46 +2,319 @@ use crate::bars::plot_halves_u64;
 use crate::bars::plot_u64;
 use crate::experiment::Experiment;
 use crate::experiment_map::ExperimentMap;
+use crate::math::bins::bin_samples;
 use crate::math::numbers::Numbers;
 
+pub struct DistrPlots {
+    pub plots: ExperimentMap<String>,
+    /// Min, middle and max values aligned with plot columns, if plotted and they fit.
+    pub axis: Option<String>,
+    /// Number of samples in a column drawn as a full block, if plotted with counts.
+    pub full_block: Option<u64>,
+    /// Percent of an experiment's samples in a column drawn as a full block,
+    /// if plotted with normalized heights.
+    pub full_block_percent: Option<f64>,
+}
+
+/// Normalized heights are fractions of this.
+const DENSITY_SCALE: u64 = 1_000_000;
+
+/// Bin counts scaled to the fraction of all samples in the bins.
+fn density(counts: &[u64]) -> Vec<u64> {
+    let total: u64 = counts.iter().sum();
+    counts
+        .iter()
+        .map(|&c| match total {
+            0 => 0,
+            _ => (c as u128 * DENSITY_SCALE as u128 / total as u128) as u64,
+        })
+        .collect()
+}
+
+/// Log scale positions are this multiple of the natural log.
+const LOG_SCALE: f64 = 1_000_000.0;
+
+/// Position of `value` on a log scale axis. Zero has no log, and is placed
+/// with one at the low edge.
+fn log_position(value: u64) -> u64 {
+    ((value.max(1) as f64).ln() * LOG_SCALE) as u64
+}
+
+fn log_value(position: u64) -> u64 {
+    (position as f64 / LOG_SCALE).exp().round() as u64
+}
+
+/// Labels of min, middle and max values spread over `width` columns,
+/// or `None` if they do not fit.
+pub(crate) fn axis_labels(
+    width: usize,
+    min: u64,
+    max: u64,
+    display: impl Fn(u64) -> String,
+) -> Option<String> {
+    let min_label = display(min);
+    let max_label = display(max);
+    let mid_label = display(min + (max - min) / 2);
+    let (min_width, mid_width, max_width) = (
+        min_label.chars().count(),
+        mid_label.chars().count(),
+        max_label.chars().count(),
+    );
+    if min_width + 1 + max_width > width {
+        return None;
+    }
+    let mut r = min_label;
+    // Centered if there is at least a space on each side.
+    let mid_start = (width / 2).saturating_sub(mid_width / 2);
+    if mid_start > min_width && mid_start + mid_width < width - max_width {
+        r.push_str(&" ".repeat(mid_start - min_width));
+        r.push_str(&mid_label);
+    }
+    let len = r.chars().count();
+    r.push_str(&" ".repeat(width - max_width - len));
+    r.push_str(&max_label);
+    Some(r)
+}
+
+/// Distribution plots on the common scale, or a placeholder when the data
+/// cannot be plotted (e.g. all samples are identical).
+///
+/// With `normalize`, bar heights are fractions of each experiment's own samples,
+/// so shapes are comparable when sample counts differ. With `log_scale`, bins are
+/// equally wide in the log of the value, and the middle axis label is the
+/// geometric mean of min and max.
 pub(crate) fn make_distr_plots(
     tests: &ExperimentMap<Experiment>,
     width: usize,
+    normalize: bool,
+    log_scale: bool,
     numbers: impl Fn(&Experiment) -> &Numbers,
-) -> anyhow::Result<ExperimentMap<String>> {
-    let min = tests
-        .values()
-        .map(|t| numbers(t).min().unwrap())
-        .min()
-        .unwrap();
-    let max = tests
-        .values()
-        .map(|t| numbers(t).max().unwrap())
-        .max()
-        .unwrap();
-
-    let distr_halves: ExperimentMap<_> =
-        tests.map(|t| (t, numbers(t).distr(width * 2, min.clone(), max.clone())));
-
-    let distr: ExperimentMap<_> =
-        tests.map(|t| (t, numbers(t).distr(width, min.clone(), max.clone())));
-
-    let max_height_halves = distr_halves
-        .values()
-        .map(|(_, d)| d.max())
-        .max()
-        .unwrap()
-        .clone();
-    let max_height = distr.values().map(|(_, d)| d.max()).max().unwrap().clone();
-
-    let distr_plots = distr.map(|(t, d)| plot_u64(&d.counts, max_height, &t.plot_highlights()));
+    display: impl Fn(u64) -> String,
+) -> DistrPlots {
+    let width = width.max(1);
+    let placeholders = |plots| DistrPlots {
+        plots,
+        axis: None,
+        full_block: None,
+        full_block_percent: None,
+    };
+
+    let min = tests.values().filter_map(|t| numbers(t).min()).min();
+    let max = tests.values().filter_map(|t| numbers(t).max()).max();
+    let (min, max) = match (min, max) {
+        (Some(min), Some(max)) if min != max => (min, max),
+        (Some(min), Some(_)) => {
+            return placeholders(tests.map(|t| match numbers(t).len() {
+                0 => "(no samples)".to_owned(),
+                _ => format!("(all samples identical: {})", display(min)),
+            }));
+        }
+        _ => return placeholders(tests.map(|_| "(no samples)".to_owned())),
+    };
+
+    let placeholder = |t: &Experiment| match numbers(t).len() {
+        0 => Some("(no samples)".to_owned()),
+        1 => Some(format!(
+            "(single sample: {})",
+            display(numbers(t).min().unwrap())
+        )),
+        _ => None,
+    };
+
+    let counts = |t: &Experiment, bins: usize| -> Vec<u64> {
+        let counts = match log_scale {
+            false => bin_samples(numbers(t).raw(), bins, (min, max)),
+            true => {
+                let positions: Vec<u64> = numbers(t).iter().map(log_position).collect();
+                bin_samples(&positions, bins, (log_position(min), log_position(max)))
+            }
+        };
+        counts.into_iter().map(|c| c as u64).collect()
+    };
+    let axis = match log_scale {
+        false => axis_labels(width, min, max, &display),
+        true => {
+            let (min_position, max_position) = (log_position(min), log_position(max));
+            axis_labels(width, min_position, max_position, |p| match p {
+                // Exact, and zero is not rounded up to one.
+                p if p == min_position => display(min),
+                p if p == max_position => display(max),
+                p => display(log_value(p)),
+            })
+        }
+    };
+
+    if normalize {
+        let densities: ExperimentMap<_> = tests.map(|t| (t, density(&counts(t, width))));
+        let max_height = densities
+            .values()
+            .flat_map(|(_, d)| d.iter())
+            .max()
+            .cloned();
+        let max_height = max_height.unwrap();
+        return DistrPlots {
+            plots: densities.map(|(t, d)| {
+                placeholder(t).unwrap_or_else(|| plot_u64(d, max_height, &t.plot_highlights()))
+            }),
+            axis,
+            full_block: None,
+            full_block_percent: Some(max_height as f64 * 100.0 / DENSITY_SCALE as f64),
+        };
+    }
+
+    let distr_halves: ExperimentMap<_> = tests.map(|t| (t, counts(t, width * 2)));
+
+    let distr: ExperimentMap<_> = tests.map(|t| (t, counts(t, width)));
+
+    let tallest = |distr: &ExperimentMap<(&Experiment, Vec<u64>)>| {
+        distr
+            .values()
+            .flat_map(|(_, d)| d.iter())
+            .max()
+            .cloned()
+            .unwrap()
+    };
+    let max_height_halves = tallest(&distr_halves);
+    let max_height = tallest(&distr);
+
+    let distr_plots = distr.map(|(t, d)| plot_u64(d, max_height, &t.plot_highlights()));
 
     let distr_halves_plots = distr_halves
-        .map(|(t, d)| plot_halves_u64(&d.counts, max_height_halves, &t.plot_halves_highlights()));
+        .map(|(t, d)| plot_halves_u64(d, max_height_halves, &t.plot_halves_highlights()));
 
-    if max_height_halves <= 2 {
-        Ok(distr_halves_plots)
+    let (plots, full_block) = if max_height_halves <= 2 {
+        (distr_halves_plots, max_height_halves)
     } else {
-        Ok(distr_plots)
+        (distr_plots, max_height)
+    };
+
+    DistrPlots {
+        plots: tests.map(|t| placeholder(t).unwrap_or_else(|| plots[t.name].clone())),
+        axis,
+        full_block: Some(full_block),
+        full_block_percent: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ansi::strip_csi;
+    use crate::distr_plot::axis_labels;
+    use crate::distr_plot::make_distr_plots;
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::measure::key::MeasureKey;
+
+    fn experiments(a: &[u64], b: &[u64]) -> ExperimentMap<Experiment> {
+        let mut tests = ExperimentMap::default();
+        for (name, numbers) in [(ExperimentName::A, a), (ExperimentName::B, b)].iter() {
+            let mut test = Experiment::new(*name, String::new(), String::new());
+            for &n in *numbers {
+                test.measures[MeasureKey::WallTime].push(n);
+            }
+            tests.insert(*name, test);
+        }
+        tests
+    }
+
+    fn plots(tests: &ExperimentMap<Experiment>) -> ExperimentMap<String> {
+        make_distr_plots(
+            tests,
+            10,
+            false,
+            false,
+            |t| &t.measures[MeasureKey::WallTime],
+            |n| n.to_string(),
+        )
+        .plots
+    }
+
+    #[test]
+    fn all_equal() {
+        let plots = plots(&experiments(&[5, 5], &[5, 5, 5]));
+        assert_eq!("(all samples identical: 5)", plots[ExperimentName::A]);
+        assert_eq!("(all samples identical: 5)", plots[ExperimentName::B]);
+    }
+
+    #[test]
+    fn single_sample() {
+        let plots = plots(&experiments(&[3], &[1, 2, 5]));
+        assert_eq!("(single sample: 3)", plots[ExperimentName::A]);
+        assert!(!plots[ExperimentName::B].starts_with('('));
+
+        let plots = self::plots(&experiments(&[], &[7]));
+        assert_eq!("(no samples)", plots[ExperimentName::A]);
+        assert_eq!("(all samples identical: 7)", plots[ExperimentName::B]);
+    }
+
+    #[test]
+    fn axis() {
+        let display = |n: u64| format!("{}ms", n);
+        assert_eq!(
+            Some("10ms      15ms      20ms".to_owned()),
+            axis_labels(24, 10, 20, display)
+        );
+        assert_eq!(
+            Some("10ms    20ms".to_owned()),
+            axis_labels(12, 10, 20, display)
+        );
+        assert_eq!(None, axis_labels(8, 10, 20, display));
+
+        let tests = experiments(&[1, 2, 5], &[3, 4]);
+        let plots = make_distr_plots(
+            &tests,
+            10,
+            false,
+            false,
+            |t| &t.measures[MeasureKey::WallTime],
+            |n| n.to_string(),
+        );
+        assert_eq!(Some("1    3   5".to_owned()), plots.axis);
+        assert_eq!(Some(1), plots.full_block);
+    }
+
+    #[test]
+    fn normalized() {
+        // Same shape, twice as many samples in B.
+        let tests = experiments(&[1, 1, 5], &[1, 1, 1, 1, 5, 5]);
+        let plots = make_distr_plots(
+            &tests,
+            3,
+            true,
+            false,
+            |t| &t.measures[MeasureKey::WallTime],
+            |n| n.to_string(),
+        );
+        let a = strip_csi(&plots.plots[ExperimentName::A]);
+        assert_eq!("█ ▄", a);
+        assert_eq!(a, strip_csi(&plots.plots[ExperimentName::B]));
+        assert_eq!(None, plots.full_block);
+        let percent = plots.full_block_percent.unwrap();
+        assert!((percent - 66.6666).abs() < 0.001, "{}", percent);
+    }
+
+    #[test]
+    fn log_scale() {
+        // Linear bins would put everything but 10000 in the first column.
+        let a: Vec<u64> = [0, 1, 10, 100, 1000, 10000].repeat(3);
+        let tests = experiments(&a, &[10, 10, 10, 100, 100, 100]);
+        let plots = make_distr_plots(
+            &tests,
+            9,
+            false,
+            true,
+            |t| &t.measures[MeasureKey::WallTime],
+            |n| n.to_string(),
+        );
+        assert_eq!(
+            "█ ▄ ▄ ▄ ▄",
+            strip_csi(&plots.plots[ExperimentName::A]).trim_end()
+        );
+        assert_eq!(
+            "  ▄ ▄",
+            strip_csi(&plots.plots[ExperimentName::B]).trim_end()
+        );
+        assert_eq!(Some("0   10000".to_owned()), plots.axis);
     }
 }
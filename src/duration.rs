@@ -1,9 +1,13 @@
+use std::convert::TryFrom;
 use std::fmt;
 use std::iter::Sum;
 use std::ops::Add;
 use std::ops::AddAssign;
 use std::ops::Div;
 use std::ops::Sub;
+use std::str::FromStr;
+
+use crate::math::sigfigs::format_sigfigs;
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Default, Debug)]
 pub struct Duration {
@@ -19,6 +23,11 @@ impl Duration {
         Self::from_nanos(nanos as u64)
     }
 
+    /// Saturating at `u64::MAX` nanoseconds, about 584 years.
+    pub fn from_std(duration: std::time::Duration) -> Duration {
+        Self::from_nanos(u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX))
+    }
+
     pub fn from_millis(millis: u64) -> Duration {
         Self::from_nanos(millis.checked_mul(1_000_000).unwrap())
     }
@@ -125,8 +134,156 @@ impl<'a> Sum<&'a Duration> for Duration {
     }
 }
 
+/// Unit used to display durations.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum TimeUnit {
+    Ns,
+    Us,
+    Ms,
+    S,
+}
+
+impl TimeUnit {
+    pub fn nanos(&self) -> u64 {
+        match self {
+            TimeUnit::Ns => 1,
+            TimeUnit::Us => 1_000,
+            TimeUnit::Ms => 1_000_000,
+            TimeUnit::S => 1_000_000_000,
+        }
+    }
+
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            TimeUnit::Ns => "ns",
+            TimeUnit::Us => "µs",
+            TimeUnit::Ms => "ms",
+            TimeUnit::S => "s",
+        }
+    }
+
+    /// Largest unit in which the duration is at least one.
+    pub fn auto(duration: Duration) -> TimeUnit {
+        [TimeUnit::S, TimeUnit::Ms, TimeUnit::Us]
+            .iter()
+            .cloned()
+            .find(|u| duration.nanos >= u.nanos())
+            .unwrap_or(TimeUnit::Ns)
+    }
+}
+
+/// Duration displayed in a fixed unit, or auto-scaled if unit is not specified.
+#[derive(Copy, Clone, Debug)]
+pub struct DurationDisplay {
+    pub duration: Duration,
+    pub unit: Option<TimeUnit>,
+    /// Significant figures instead of three decimal places.
+    pub sigfigs: Option<usize>,
+}
+
+impl DurationDisplay {
+    pub fn with_sigfigs(self, sigfigs: Option<usize>) -> DurationDisplay {
+        DurationDisplay { sigfigs, ..self }
+    }
+}
+
+impl fmt::Display for DurationDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = self.unit.unwrap_or_else(|| TimeUnit::auto(self.duration));
+        if let Some(sigfigs) = self.sigfigs {
+            let value = self.duration.nanos as f64 / unit.nanos() as f64;
+            return write!(f, "{}{}", format_sigfigs(value, sigfigs), unit.suffix());
+        }
+        match unit {
+            TimeUnit::Ns => write!(f, "{}{}", self.duration.nanos, unit.suffix()),
+            _ => write!(
+                f,
+                "{:.3}{}",
+                self.duration.nanos as f64 / unit.nanos() as f64,
+                unit.suffix()
+            ),
+        }
+    }
+}
+
+impl Duration {
+    pub fn display_in(self, unit: Option<TimeUnit>) -> DurationDisplay {
+        DurationDisplay {
+            duration: self,
+            unit,
+            sigfigs: None,
+        }
+    }
+}
+
+/// Auto-scaled, e.g. `3.214ms` or `1.500s`.
 impl fmt::Display for Duration {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}.{:03}", self.millis() / 1000, self.millis() % 1000)
+        fmt::Display::fmt(&self.display_in(None), f)
+    }
+}
+
+impl FromStr for Duration {
+    type Err = anyhow::Error;
+
+    /// Parse duration like `1.5`, `30s`, `500ms`, `5m` or `2h`. Default unit is seconds.
+    fn from_str(s: &str) -> anyhow::Result<Duration> {
+        let (number, unit_seconds) = if let Some(n) = s.strip_suffix("ms") {
+            (n, 0.001)
+        } else if let Some(n) = s.strip_suffix('s') {
+            (n, 1.0)
+        } else if let Some(n) = s.strip_suffix('m') {
+            (n, 60.0)
+        } else if let Some(n) = s.strip_suffix('h') {
+            (n, 3600.0)
+        } else {
+            (s, 1.0)
+        };
+        let number: f64 = number
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid duration: {:?}", s))?;
+        if number < 0.0 || !number.is_finite() {
+            return Err(anyhow::anyhow!("invalid duration: {:?}", s));
+        }
+        Ok(Duration::from_seconds_f64(number * unit_seconds))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::duration::Duration;
+    use crate::duration::TimeUnit;
+
+    #[test]
+    fn display() {
+        assert_eq!("812ns", Duration::from_nanos(812).to_string());
+        assert_eq!("1.500µs", Duration::from_nanos(1500).to_string());
+        assert_eq!("3.214ms", Duration::from_nanos(3_214_000).to_string());
+        assert_eq!("2.000s", Duration::from_millis(2000).to_string());
+        assert_eq!(
+            "0.003s",
+            Duration::from_millis(3)
+                .display_in(Some(TimeUnit::S))
+                .to_string()
+        );
+        assert_eq!(
+            "3.2ms",
+            Duration::from_nanos(3_214_000)
+                .display_in(None)
+                .with_sigfigs(Some(2))
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(Duration::from_millis(1500), "1.5".parse().unwrap());
+        assert_eq!(Duration::from_millis(30_000), "30s".parse().unwrap());
+        assert_eq!(Duration::from_millis(500), "500ms".parse().unwrap());
+        assert_eq!(Duration::from_millis(300_000), "5m".parse().unwrap());
+        assert_eq!(Duration::from_millis(7_200_000), "2h".parse().unwrap());
+        assert!("".parse::<Duration>().is_err());
+        assert!("-1s".parse::<Duration>().is_err());
+        assert!("1d".parse::<Duration>().is_err());
     }
 }
@@ -0,0 +1,119 @@
+//! Machine a session ran on, written to `env.txt` so merged results from
+//! different machines can be noticed.
+
+use std::fs;
+use std::thread;
+
+/// `key: value` fields, like `cpu: Intel(R) Core(TM) i7-8700 CPU @ 3.20GHz`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EnvSnapshot {
+    pub fields: Vec<(String, String)>,
+}
+
+/// CPU model from `/proc/cpuinfo`.
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo
+        .lines()
+        .filter_map(|l| l.split_once(':'))
+        .find(|(key, _)| key.trim() == "model name")
+        .map(|(_, value)| value.trim().to_owned())
+}
+
+/// Kernel release, e.g. `6.1.0-13-amd64`.
+#[cfg(unix)]
+fn kernel_release() -> Option<String> {
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return None;
+    }
+    let release = unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) };
+    Some(release.to_string_lossy().into_owned())
+}
+
+#[cfg(not(unix))]
+fn kernel_release() -> Option<String> {
+    None
+}
+
+impl EnvSnapshot {
+    /// Snapshot of the current machine; fields which cannot be read are omitted.
+    pub fn capture() -> EnvSnapshot {
+        let mut fields = vec![
+            ("os".to_owned(), std::env::consts::OS.to_owned()),
+            ("arch".to_owned(), std::env::consts::ARCH.to_owned()),
+        ];
+        if let Some(cpu) = cpu_model() {
+            fields.push(("cpu".to_owned(), cpu));
+        }
+        if let Ok(cpus) = thread::available_parallelism() {
+            fields.push(("cpus".to_owned(), cpus.to_string()));
+        }
+        if let Some(kernel) = kernel_release() {
+            fields.push(("kernel".to_owned(), kernel));
+        }
+        EnvSnapshot { fields }
+    }
+
+    pub fn parse(text: &str) -> EnvSnapshot {
+        EnvSnapshot {
+            fields: text
+                .lines()
+                .filter_map(|l| l.split_once(": "))
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        self.fields
+            .iter()
+            .map(|(key, value)| format!("{}: {}\n", key, value))
+            .collect()
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Fields with different values, like `cpu: "X" vs "Y"`. A field missing
+    /// from either snapshot is not a difference, e.g. CPU model on macOS.
+    pub fn differences(&self, other: &EnvSnapshot) -> Vec<String> {
+        self.fields
+            .iter()
+            .filter_map(|(key, value)| {
+                let other = other.get(key)?;
+                if other == value {
+                    return None;
+                }
+                Some(format!("{}: {:?} vs {:?}", key, value, other))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::env_snapshot::EnvSnapshot;
+
+    #[test]
+    fn differences() {
+        let laptop = EnvSnapshot::parse("os: linux\ncpu: i7-8550U\ncpus: 8\nkernel: 6.1\n");
+        assert_eq!(
+            "os: linux\ncpu: i7-8550U\ncpus: 8\nkernel: 6.1\n",
+            laptop.to_text()
+        );
+        let server = EnvSnapshot::parse("os: linux\ncpu: EPYC 7763\ncpus: 128\n");
+        assert_eq!(
+            vec![
+                "cpu: \"i7-8550U\" vs \"EPYC 7763\"".to_owned(),
+                "cpus: \"8\" vs \"128\"".to_owned(),
+            ],
+            laptop.differences(&server)
+        );
+        assert!(laptop.differences(&laptop.clone()).is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+//! Structured session events, written to `events.jsonl` with `--log-format json`.
+
+use std::fmt::Write;
+
+use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::math::stats::Stats;
+
+/// Something that happened during a session, one JSON object per line.
+pub enum Event<'a> {
+    /// Measured run of the experiment is about to start, after its warmup.
+    RunStart { experiment: ExperimentName },
+    /// Measured run succeeded.
+    RunResult {
+        experiment: ExperimentName,
+        wall_time_ns: u64,
+        /// Not reported on some platforms.
+        max_rss_bytes: Option<u64>,
+        metric: Option<u64>,
+    },
+    /// Warmup or measured run failed and was not recorded.
+    RunFailed {
+        experiment: ExperimentName,
+        error: &'a str,
+    },
+    /// Stats of a measure were rendered.
+    Stats {
+        /// Measure id, e.g. `wall-time`.
+        measure: &'a str,
+        stats: &'a ExperimentMap<Stats<u64>>,
+    },
+    /// Session ended.
+    Stop { reason: &'a str },
+}
+
+/// JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut r = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => r.push_str("\\\""),
+            '\\' => r.push_str("\\\\"),
+            '\n' => r.push_str("\\n"),
+            '\t' => r.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(r, "\\u{:04x}", c as u32).unwrap(),
+            c => r.push(c),
+        }
+    }
+    r.push('"');
+    r
+}
+
+fn json_option(v: Option<u64>) -> String {
+    match v {
+        Some(v) => v.to_string(),
+        None => "null".to_owned(),
+    }
+}
+
+impl Event<'_> {
+    /// Single line JSON object with `event` type, and `time_ms` since Unix epoch.
+    pub fn to_json(&self, time_ms: u64) -> String {
+        let (event, fields) = match self {
+            Event::RunStart { experiment } => (
+                "run_start",
+                format!("\"experiment\":{}", json_string(experiment.name())),
+            ),
+            Event::RunResult {
+                experiment,
+                wall_time_ns,
+                max_rss_bytes,
+                metric,
+            } => (
+                "run_result",
+                format!(
+                    "\"experiment\":{},\"wall_time_ns\":{},\"max_rss_bytes\":{},\"metric\":{}",
+                    json_string(experiment.name()),
+                    wall_time_ns,
+                    json_option(*max_rss_bytes),
+                    json_option(*metric),
+                ),
+            ),
+            Event::RunFailed { experiment, error } => (
+                "run_failed",
+                format!(
+                    "\"experiment\":{},\"error\":{}",
+                    json_string(experiment.name()),
+                    json_string(error),
+                ),
+            ),
+            Event::Stats { measure, stats } => {
+                let experiments: Vec<String> = stats
+                    .iter()
+                    .map(|(name, s)| {
+                        format!(
+                            "{}:{{\"n\":{},\"mean\":{},\"std\":{},\"min\":{},\"med\":{},\"max\":{},\"cv_percent\":{}}}",
+                            json_string(name.name()),
+                            s.count,
+                            s.mean,
+                            s.std,
+                            s.min,
+                            s.med,
+                            s.max,
+                            match s.cv_percent() {
+                                Some(cv) => format!("{:.3}", cv),
+                                None => "null".to_owned(),
+                            },
+                        )
+                    })
+                    .collect();
+                (
+                    "stats",
+                    format!(
+                        "\"measure\":{},\"experiments\":{{{}}}",
+                        json_string(measure),
+                        experiments.join(","),
+                    ),
+                )
+            }
+            Event::Stop { reason } => ("stop", format!("\"reason\":{}", json_string(reason))),
+        };
+        format!(
+            "{{\"event\":\"{}\",\"time_ms\":{},{}}}",
+            event, time_ms, fields
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::Event;
+    use crate::event::json_string;
+    use crate::experiment_name::ExperimentName;
+
+    #[test]
+    fn escape() {
+        assert_eq!(r#""a\"b\\c\n\u0001""#, json_string("a\"b\\c\n\x01"));
+    }
+
+    #[test]
+    fn run_result() {
+        let event = Event::RunResult {
+            experiment: ExperimentName::B,
+            wall_time_ns: 1500,
+            max_rss_bytes: None,
+            metric: Some(7),
+        };
+        assert_eq!(
+            r#"{"event":"run_result","time_ms":10,"experiment":"B","wall_time_ns":1500,"max_rss_bytes":null,"metric":7}"#,
+            event.to_json(10)
+        );
+    }
+}
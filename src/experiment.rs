@@ -1,34 +1,149 @@
 use crate::ansi;
 use crate::bars::PlotHighlight;
+use crate::experiment_map::ExperimentMap;
 use crate::experiment_name::ExperimentName;
 use crate::math::numbers::Numbers;
+use crate::measure::key::MeasureKey;
 use crate::measure::map::MeasureMap;
 
+#[derive(Clone)]
 pub struct Experiment {
     pub name: ExperimentName,
+    /// Label chosen by user, like `baseline`, or the letter.
+    ///
+    /// The letter stays the key in logs and machine-readable output.
+    pub label: String,
     pub warmup: String,
     pub run: String,
     pub measures: MeasureMap<Numbers>,
+    /// Start time of each sample in `measures`, milliseconds since Unix epoch.
+    pub timestamps: MeasureMap<Vec<u64>>,
+    /// Measures not recorded for this experiment, e.g. max RSS without `--mem-b`.
+    pub skipped: MeasureMap<bool>,
+    /// Number of times the experiment was run, including failures.
+    pub attempts: usize,
+    /// Number of successful runs, including ignored ones.
+    pub successes: usize,
+    /// Description of the last failure.
+    pub last_failure: Option<String>,
+    /// Successful runs discarded because the measured duration was implausible.
+    pub clock_anomalies: usize,
+    /// Successful runs discarded because the machine slept during the run.
+    pub sleep_discards: usize,
+    /// Time spent in warmup before each wall time sample in `raw`, nanoseconds.
+    pub warmup_times: Vec<u64>,
 }
 
 impl Experiment {
+    pub fn new(name: ExperimentName, warmup: String, run: String) -> Experiment {
+        Experiment {
+            name,
+            label: name.name().to_owned(),
+            warmup,
+            run,
+            measures: MeasureMap::new_all_default(),
+            timestamps: MeasureMap::new_all_default(),
+            skipped: MeasureMap::new_all_default(),
+            attempts: 0,
+            successes: 0,
+            last_failure: None,
+            clock_anomalies: 0,
+            sleep_discards: 0,
+            warmup_times: Vec::new(),
+        }
+    }
+
+    pub fn name_colored(&self) -> String {
+        self.name.colored(&self.label)
+    }
+
     pub fn plot_highlights(&self) -> PlotHighlight {
         PlotHighlight {
-            non_zero: format!("{}", self.name.color().to_owned()),
-            zero: format!("{}", ansi::WHITE_BG),
+            non_zero: self.name.color().to_owned(),
+            zero: ansi::WHITE_BG.to_owned(),
             reset: ansi::RESET.to_owned(),
         }
     }
 
     pub fn plot_halves_highlights(&self) -> PlotHighlight {
         PlotHighlight {
-            non_zero: format!("{}", self.name.color().to_owned()),
+            non_zero: self.name.color().to_owned(),
             zero: "".to_owned(),
             reset: ansi::RESET.to_owned(),
         }
     }
 
+    /// Copy with outliers dropped from measures having at least `min_n` samples.
+    pub fn without_outliers(&self, min_n: usize) -> Experiment {
+        Experiment {
+            name: self.name,
+            label: self.label.clone(),
+            warmup: self.warmup.clone(),
+            run: self.run.clone(),
+            measures: self.measures.map(|n| {
+                if n.len() >= min_n {
+                    n.without_outliers()
+                } else {
+                    n.clone()
+                }
+            }),
+            timestamps: self.timestamps.map(|t| t.clone()),
+            skipped: self.skipped.clone(),
+            attempts: self.attempts,
+            successes: self.successes,
+            last_failure: self.last_failure.clone(),
+            clock_anomalies: self.clock_anomalies,
+            sleep_discards: self.sleep_discards,
+            warmup_times: self.warmup_times.clone(),
+        }
+    }
+
+    pub fn records(&self, key: MeasureKey) -> bool {
+        !self.skipped[key]
+    }
+
+    /// Keep a bounded sample of each measure, see `Numbers::streaming`.
+    pub fn set_streaming(&mut self, capacity: usize) {
+        for numbers in self.measures.values_mut() {
+            *numbers = Numbers::streaming(capacity);
+        }
+    }
+
+    /// Record a sample of a run started at `timestamp_millis`.
+    ///
+    /// Returns the index of the sample in `raw`, `None` if not kept in streaming mode.
+    pub fn push(&mut self, key: MeasureKey, value: u64, timestamp_millis: u64) -> Option<usize> {
+        let index = self.measures[key].push(value)?;
+        set_or_push(&mut self.timestamps[key], index, timestamp_millis);
+        Some(index)
+    }
+
+    /// Forget all samples.
+    pub fn clear(&mut self) {
+        for numbers in self.measures.values_mut() {
+            numbers.clear();
+        }
+        for timestamps in self.timestamps.values_mut() {
+            timestamps.clear();
+        }
+        self.warmup_times.clear();
+    }
+
     pub fn runs(&self) -> usize {
         self.measures.values().next().unwrap().len()
     }
 }
+
+/// Store `value` at `index`, which is at most `v.len()`.
+pub fn set_or_push(v: &mut Vec<u64>, index: usize, value: u64) {
+    if index == v.len() {
+        v.push(value);
+    } else {
+        v[index] = value;
+    }
+}
+
+/// Experiments which record the measure.
+pub fn recording(tests: &ExperimentMap<Experiment>, key: MeasureKey) -> ExperimentMap<Experiment> {
+    tests.filter_map(|t| Some(t.clone()).filter(|t| t.records(key)))
+}
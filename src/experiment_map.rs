@@ -64,10 +64,20 @@ impl<A> ExperimentMap<A> {
         }
     }
 
+    pub fn filter_map<'a, B>(&'a self, mut f: impl FnMut(&'a A) -> Option<B>) -> ExperimentMap<B> {
+        let mut r = ExperimentMap::default();
+        for (name, value) in self.iter() {
+            if let Some(value) = f(value) {
+                r.insert(name, value);
+            }
+        }
+        r
+    }
+
     pub fn zip<'a, B>(
         &'a self,
         other: &'a ExperimentMap<B>,
-    ) -> impl Iterator<Item = (ExperimentName, &A, &B)> + 'a {
+    ) -> impl Iterator<Item = (ExperimentName, &'a A, &'a B)> + 'a {
         self.values
             .zip(&other.values)
             .map(|(k, a, b)| (ExperimentName::from_index(k), a, b))
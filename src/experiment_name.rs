@@ -1,5 +1,7 @@
 use std::fmt;
 
+use once_cell::sync::Lazy;
+
 use crate::ansi;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -12,6 +14,9 @@ pub enum ExperimentName {
 }
 
 impl ExperimentName {
+    /// Number of experiments.
+    pub const COUNT: usize = 5;
+
     pub fn index(&self) -> usize {
         match self {
             ExperimentName::A => 0,
@@ -33,7 +38,15 @@ impl ExperimentName {
         }
     }
 
-    pub fn name(&self) -> &str {
+    /// Parse letter, like `A`.
+    pub fn from_name(name: &str) -> Option<ExperimentName> {
+        (0..ExperimentName::COUNT)
+            .map(ExperimentName::from_index)
+            .find(|n| n.name() == name)
+    }
+
+    /// Letter, like `A`.
+    pub fn name(&self) -> &'static str {
         match self {
             ExperimentName::A => "A",
             ExperimentName::B => "B",
@@ -43,7 +56,22 @@ impl ExperimentName {
         }
     }
 
+    /// Terminal color, distinct hues from a 24-bit palette if the terminal supports it.
     pub fn color(&self) -> &'static str {
+        static TRUECOLOR: Lazy<Option<Vec<String>>> = Lazy::new(|| {
+            if ansi::truecolor_supported() {
+                Some(
+                    (0..ExperimentName::COUNT)
+                        .map(|i| ansi::fg_rgb(ansi::palette_rgb(i, ExperimentName::COUNT)))
+                        .collect(),
+                )
+            } else {
+                None
+            }
+        });
+        if let Some(palette) = &*TRUECOLOR {
+            return &palette[self.index()];
+        }
         match self {
             ExperimentName::A => ansi::RED,
             ExperimentName::B => ansi::GREEN,
@@ -53,8 +81,20 @@ impl ExperimentName {
         }
     }
 
-    pub fn name_colored(&self) -> String {
-        format!("{}{}{}", self.color(), self.name(), ansi::RESET)
+    /// Color for HTML and SVG output, matching `color`.
+    pub fn css_color(&self) -> &'static str {
+        match self {
+            ExperimentName::A => "#cc0000",
+            ExperimentName::B => "#00a000",
+            ExperimentName::C => "#0000cc",
+            ExperimentName::D => "#b000b0",
+            ExperimentName::E => "#00a0a0",
+        }
+    }
+
+    /// Label of this experiment, see `Experiment::label`, in its color.
+    pub fn colored(&self, label: &str) -> String {
+        format!("{}{}{}", self.color(), label, ansi::RESET)
     }
 }
 
@@ -0,0 +1,138 @@
+//! Self-contained HTML report.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::experiment::Experiment;
+use crate::experiment::recording;
+use crate::experiment_map::ExperimentMap;
+use crate::fs_util::write_using_temp;
+use crate::measure::tr::AllMeasures;
+use crate::summary::SummaryReport;
+use crate::svg::escape;
+use crate::svg::stack;
+
+const HISTOGRAM_BUCKETS: usize = 60;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: right; }
+th:first-child, td:first-child { text-align: left; font-weight: bold; }
+pre { background: #f4f4f4; padding: 0.5em; }
+";
+
+/// Render report with stats table and histogram for each measure.
+pub fn html_report(
+    args: &str,
+    measures: &AllMeasures,
+    tests: &ExperimentMap<Experiment>,
+    summary: &SummaryReport,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r, "<!DOCTYPE html>")?;
+    writeln!(r, "<html>")?;
+    writeln!(r, "<head>")?;
+    writeln!(r, "<meta charset=\"utf-8\">")?;
+    writeln!(r, "<title>absh report</title>")?;
+    writeln!(r, "<style>\n{}</style>", STYLE)?;
+    writeln!(r, "</head>")?;
+    writeln!(r, "<body>")?;
+    writeln!(r, "<h1>absh report</h1>")?;
+    writeln!(r, "<pre>{}</pre>", escape(args))?;
+
+    writeln!(r, "<table>")?;
+    for test in tests.values() {
+        writeln!(
+            r,
+            "<tr><td style=\"color: {}\">{}</td><td style=\"text-align: left\"><code>{}</code></td></tr>",
+            test.name.css_color(),
+            escape(&test.label),
+            escape(&test.run),
+        )?;
+    }
+    writeln!(r, "</table>")?;
+
+    for measure_summary in &summary.measures {
+        let measure = measures.get(measure_summary.key).unwrap();
+        writeln!(r, "<h2>{}</h2>", escape(measure.name()))?;
+        // Experiments without the measure are omitted.
+        let tests = &recording(tests, measure_summary.key);
+        let columns = measure.stats_columns(tests);
+        writeln!(r, "<table>")?;
+        write!(r, "<tr><th></th>")?;
+        for (name, _) in columns.values().next().unwrap() {
+            write!(r, "<th>{}</th>", name)?;
+        }
+        writeln!(r, "</tr>")?;
+        for (name, columns) in columns.iter() {
+            write!(
+                r,
+                "<tr><td style=\"color: {}\">{}</td>",
+                name.css_color(),
+                escape(&tests[name].label)
+            )?;
+            for (_, value) in columns {
+                write!(r, "<td>{}</td>", escape(value))?;
+            }
+            writeln!(r, "</tr>")?;
+        }
+        writeln!(r, "</table>")?;
+        if !measure_summary.comparisons.is_empty() {
+            writeln!(r, "<table>")?;
+            writeln!(
+                r,
+                "<tr><th></th><th>ratio</th><th>95% conf</th><th>p</th></tr>"
+            )?;
+            for c in &measure_summary.comparisons {
+                writeln!(
+                    r,
+                    "<tr><td>{}/{}</td><td>{:.3}</td><td>{:.3}..{:.3}</td><td>{:.3}</td></tr>",
+                    escape(summary.label(c.other)),
+                    escape(summary.label(c.base)),
+                    c.ratio,
+                    c.ratio_min,
+                    c.ratio_max,
+                    c.p_value,
+                )?;
+            }
+            writeln!(r, "</table>")?;
+        }
+        writeln!(r, "{}", measure.histogram_svg(tests, HISTOGRAM_BUCKETS))?;
+    }
+
+    writeln!(r, "</body>")?;
+    writeln!(r, "</html>")?;
+    Ok(r)
+}
+
+/// Histogram of each measure in `summary` as a single SVG image.
+pub fn graph_svg(
+    measures: &AllMeasures,
+    tests: &ExperimentMap<Experiment>,
+    summary: &SummaryReport,
+) -> String {
+    let sections: Vec<(String, String)> = summary
+        .measures
+        .iter()
+        .map(|measure_summary| {
+            let measure = measures.get(measure_summary.key).unwrap();
+            let tests = &recording(tests, measure_summary.key);
+            (
+                measure.name().to_owned(),
+                measure.histogram_svg(tests, HISTOGRAM_BUCKETS),
+            )
+        })
+        .collect();
+    stack(&sections)
+}
+
+pub fn write_html_report(
+    path: &Path,
+    args: &str,
+    measures: &AllMeasures,
+    tests: &ExperimentMap<Experiment>,
+    summary: &SummaryReport,
+) -> anyhow::Result<()> {
+    write_using_temp(path, html_report(args, measures, tests, summary)?)
+}
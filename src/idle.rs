@@ -0,0 +1,77 @@
+//! Wait for the machine to become idle to reduce thermal throttling.
+
+use std::fs;
+use std::thread;
+use std::time::Instant;
+
+use crate::duration::Duration;
+use crate::shutdown;
+
+/// Machine is considered idle when both values are below thresholds.
+pub struct IdleThresholds {
+    /// One minute load average.
+    pub load: f64,
+    /// Max temperature of thermal zones, used where available.
+    pub temp_celsius: f64,
+    /// Give up waiting after this time.
+    pub max_wait: Duration,
+}
+
+/// One minute load average.
+#[cfg(unix)]
+pub fn load_average() -> Option<f64> {
+    let mut load = [0.0; 1];
+    match unsafe { libc::getloadavg(load.as_mut_ptr(), 1) } {
+        1 => Some(load[0]),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn load_average() -> Option<f64> {
+    None
+}
+
+/// Max temperature of `/sys/class/thermal` zones in degrees Celsius.
+pub fn max_temp_celsius() -> Option<f64> {
+    fs::read_dir("/sys/class/thermal")
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with("thermal_zone"))
+        .filter_map(|e| fs::read_to_string(e.path().join("temp")).ok())
+        .filter_map(|t| t.trim().parse::<i64>().ok())
+        .map(|millis| millis as f64 / 1000.0)
+        .fold(None, |max: Option<f64>, t| {
+            Some(max.map_or(t, |m| m.max(t)))
+        })
+}
+
+pub struct IdleWait {
+    pub waited: std::time::Duration,
+    /// False if gave up waiting.
+    pub idle: bool,
+    pub load: Option<f64>,
+    pub temp_celsius: Option<f64>,
+}
+
+/// Sleep until the machine is idle or `max_wait` elapses.
+pub fn wait_for_idle(thresholds: &IdleThresholds) -> IdleWait {
+    let start = Instant::now();
+    let max_wait = std::time::Duration::from_nanos(thresholds.max_wait.nanos());
+    loop {
+        let load = load_average();
+        let temp_celsius = max_temp_celsius();
+        let idle = load.is_none_or(|l| l < thresholds.load)
+            && temp_celsius.is_none_or(|t| t < thresholds.temp_celsius);
+        let waited = start.elapsed();
+        if idle || waited >= max_wait || shutdown::requested().is_some() {
+            return IdleWait {
+                waited,
+                idle,
+                load,
+                temp_celsius,
+            };
+        }
+        thread::sleep(std::time::Duration::from_millis(500));
+    }
+}
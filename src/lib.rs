@@ -1,19 +1,55 @@
 pub mod ansi;
 mod bars;
+pub mod capture;
 pub mod console_writer;
 pub mod distr_plot;
 pub mod duration;
+pub mod env_snapshot;
+pub mod event;
 pub mod experiment;
 pub mod experiment_map;
 pub mod experiment_name;
 pub mod fs_util;
+pub mod html_report;
+pub mod idle;
 pub mod linear_map;
 pub mod math;
 pub mod maybe_strip_csi_writer;
 pub mod measure;
 pub mod mem_usage;
+pub mod merge;
+pub mod oneline;
+pub mod order;
+pub mod prometheus;
 pub mod render_stats;
+pub mod rss_sampler;
 pub mod run_log;
+pub mod runner;
+pub mod server;
 pub mod sh;
 pub mod shell;
+pub mod shutdown;
 pub mod student;
+pub mod summary;
+pub mod svg;
+pub mod table;
+pub mod tap;
+pub mod target;
+pub mod timeout;
+
+pub use crate::runner::BenchmarkResults;
+pub use crate::runner::CompletedRun;
+pub use crate::runner::RunConfig;
+pub use crate::runner::run;
+
+/// Crate version with git hash, build date and target, set by `build.rs`.
+pub const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("ABSH_GIT_HASH"),
+    " ",
+    env!("ABSH_BUILD_DATE"),
+    " ",
+    env!("ABSH_TARGET"),
+    ")"
+);
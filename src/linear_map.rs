@@ -1,6 +1,7 @@
 use std::ops::Index;
 
 /// Map small integer to a value.
+#[derive(Clone)]
 pub struct LinearMap<A> {
     values: Vec<Option<A>>,
 }
@@ -68,7 +69,7 @@ impl<A> LinearMap<A> {
     pub fn zip<'a, B>(
         &'a self,
         other: &'a LinearMap<B>,
-    ) -> impl Iterator<Item = (usize, &A, &B)> + 'a {
+    ) -> impl Iterator<Item = (usize, &'a A, &'a B)> + 'a {
         self.iter().zip(other.iter()).map(|((ka, va), (kb, vb))| {
             assert_eq!(ka, kb);
             (ka, va, vb)
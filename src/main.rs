@@ -1,29 +1,50 @@
-use std::convert::TryInto;
 use std::fmt::Write as _;
-use std::time::Instant;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
 
+use absh::CompletedRun;
+use absh::RunConfig;
 use absh::ansi;
+use absh::console_writer::ColorChoice;
+use absh::console_writer::ConsoleWriter;
 use absh::duration::Duration;
-use absh::experiment::Experiment;
-use absh::experiment_map::ExperimentMap;
+use absh::duration::TimeUnit;
 use absh::experiment_name::ExperimentName;
+use absh::measure::direction::Direction;
 use absh::measure::key::MeasureKey;
-use absh::measure::map::MeasureMap;
-use absh::measure::tr::AllMeasures;
-use absh::measure::tr::MaxRss;
-use absh::measure::tr::MeasureDyn;
-use absh::measure::tr::WallTime;
-use absh::mem_usage::MemUsage;
-use absh::run_log::RunLog;
-use absh::sh::spawn_sh;
+use absh::merge::merge;
+use absh::render_stats::Palette;
+use absh::render_stats::RenderOptions;
+use absh::render_stats::SortKey;
+use absh::render_stats::TableStyle;
+use absh::run;
+use absh::run_log::LogFormat;
+use absh::run_log::Verbosity;
+use absh::runner::MetricScope;
+use absh::runner::OnRun;
+use absh::runner::STREAMING_RESERVOIR_SIZE;
+use absh::runner::ScriptFailed;
+use absh::summary::SummaryReport;
+use absh::target::ScoreWeights;
+use absh::target::Target;
+use absh::target::WorseLimit;
+use absh::timeout::Signal;
 use clap::Parser;
-use rand::prelude::SliceRandom;
-use wait4::Wait4;
 
 #[derive(clap::Parser, Debug)]
-#[command(about = "A/B testing for shell scripts")]
+#[command(
+    group(clap::ArgGroup::new("metric_source").args(["metric", "metric_file"])),
+    about = "A/B testing for shell scripts",
+    version = absh::VERSION,
+    after_help = "Warmup and run scripts get ABSH_TMPDIR, a directory shared by them and removed \
+        after each run.\n\nUse `absh merge DIR...` to combine samples of several runs."
+)]
 struct Opts {
-    #[clap(short, help = "A variant shell script")]
+    #[clap(short, value_parser = parse_script, help = "A variant shell script")]
     a: String,
     #[clap(short, help = "B variant shell script")]
     b: Option<String>,
@@ -43,224 +64,993 @@ struct Opts {
     dw: Option<String>,
     #[clap(short = 'E', long = "e-warmup", help = "E variant warmup shell script")]
     ew: Option<String>,
-    #[clap(short = 'r', help = "Randomise test execution order")]
+    #[clap(
+        long,
+        value_name = "LABEL",
+        value_parser = parse_label,
+        help = "Label shown instead of A, e.g. `baseline`"
+    )]
+    name_a: Option<String>,
+    #[clap(long, value_name = "LABEL", value_parser = parse_label, help = "Label shown instead of B")]
+    name_b: Option<String>,
+    #[clap(long, value_name = "LABEL", value_parser = parse_label, help = "Label shown instead of C")]
+    name_c: Option<String>,
+    #[clap(long, value_name = "LABEL", value_parser = parse_label, help = "Label shown instead of D")]
+    name_d: Option<String>,
+    #[clap(long, value_name = "LABEL", value_parser = parse_label, help = "Label shown instead of E")]
+    name_e: Option<String>,
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "1",
+        value_parser = parse_at_least_one,
+        help = "Run warmup scripts at least N times before each run"
+    )]
+    warmup_runs: u32,
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        default_value = "0",
+        help = "Repeat warmup scripts before each run until they took at least this long, \
+            combined with --warmup-runs"
+    )]
+    warmup_time: Duration,
+    #[clap(
+        long,
+        value_name = "SCRIPT",
+        help = "Shell script run once before all iterations, e.g. to fill caches"
+    )]
+    global_warmup: Option<String>,
+    #[clap(
+        long,
+        help = "Run the global and each experiment's warmup script, e.g. to prime caches \
+            in CI, and exit without measuring"
+    )]
+    warmup_only: bool,
+    #[clap(
+        long,
+        value_name = "SCRIPT",
+        help = "Shell script run untimed after each run to reset system state, \
+            e.g. to drop caches; failure is only a warning"
+    )]
+    between: Option<String>,
+    #[clap(
+        short = 'r',
+        help = "Randomise test execution order (saved to order.txt in the log directory)"
+    )]
     random_order: bool,
-    #[clap(short = 'i', help = "Ignore the results of the first iteration")]
-    ignore_first: bool,
+    #[clap(
+        long,
+        value_name = "FILE",
+        help = "Run pairs in the order from the file, e.g. order.txt of a previous session"
+    )]
+    replay_order: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "NAMES",
+        value_delimiter = ',',
+        value_parser = parse_experiment_name,
+        conflicts_with = "replay_order",
+        help = "Run experiments of each pair in this order, e.g. `b,a,c`, overriding -r"
+    )]
+    order: Option<Vec<ExperimentName>>,
+    #[clap(
+        short = 'i',
+        help = "Ignore the results of the first iteration, same as `--ignore-first 1`"
+    )]
+    ignore_first_one: bool,
+    #[clap(
+        long = "ignore-first",
+        value_name = "K",
+        conflicts_with = "ignore_first_one",
+        help = "Ignore the results of the first K iterations"
+    )]
+    ignore_first: Option<u32>,
     #[clap(
         short = 'n',
-        help = "Stop after n successful iterations (run forever if not specified)"
+        long = "max-iters",
+        value_parser = parse_at_least_one,
+        help = "Stop after n successful iterations of every experiment, failed runs are not \
+            counted (run forever if not specified)"
     )]
     iterations: Option<u32>,
+    #[clap(
+        long = "min-iters",
+        value_name = "N",
+        default_value = "0",
+        help = "Do not stop for --max-duration or --auto before every experiment has \
+            N successful iterations"
+    )]
+    min_iterations: u32,
+    #[clap(
+        long,
+        help = "Do not offer to continue with more iterations when -n is reached \
+            on a terminal"
+    )]
+    no_prompt: bool,
+    #[clap(
+        long,
+        value_name = "PERCENT",
+        value_parser = parse_positive_percent,
+        help = "Stop running each experiment once the 95% confidence interval of its mean time \
+            is within PERCENT of the mean, -n caps the number of runs"
+    )]
+    auto: Option<f64>,
     #[clap(short = 'm', long, help = "Also measure max resident set size")]
     mem: bool,
+    #[clap(long, help = "Measure max resident set size of A only")]
+    mem_a: bool,
+    #[clap(long, help = "Measure max resident set size of B only")]
+    mem_b: bool,
+    #[clap(long, help = "Measure max resident set size of C only")]
+    mem_c: bool,
+    #[clap(long, help = "Measure max resident set size of D only")]
+    mem_d: bool,
+    #[clap(long, help = "Measure max resident set size of E only")]
+    mem_e: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "1",
+        value_parser = parse_at_least_one,
+        conflicts_with = "load",
+        help = "Run A N times in each measured run and record the mean, e.g. to time \
+            a fast experiment more precisely; per-run times stay comparable with other experiments"
+    )]
+    inner_a: u32,
+    #[clap(long, value_name = "N", default_value = "1", value_parser = parse_at_least_one, conflicts_with = "load", help = "Like --inner-a for B")]
+    inner_b: u32,
+    #[clap(long, value_name = "N", default_value = "1", value_parser = parse_at_least_one, conflicts_with = "load", help = "Like --inner-a for C")]
+    inner_c: u32,
+    #[clap(long, value_name = "N", default_value = "1", value_parser = parse_at_least_one, conflicts_with = "load", help = "Like --inner-a for D")]
+    inner_d: u32,
+    #[clap(long, value_name = "N", default_value = "1", value_parser = parse_at_least_one, conflicts_with = "load", help = "Like --inner-a for E")]
+    inner_e: u32,
+    #[clap(
+        long,
+        help = "Sample RSS while each run is in progress and log its average next to the peak \
+            (Linux only)"
+    )]
+    avg_rss: bool,
+    #[clap(
+        long,
+        help = "Script run after each successful run, which prints a number to record"
+    )]
+    metric: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with = "metric",
+        help = "Read the user defined metric from this file after each successful run, \
+            e.g. written by the run script, instead of running --metric"
+    )]
+    metric_file: Option<PathBuf>,
+    #[clap(
+        long,
+        requires = "metric_file",
+        help = "Delete --metric-file after reading it, so a stale value is not read again"
+    )]
+    metric_file_delete: bool,
+    #[clap(long, help = "Name of the user defined metric")]
+    metric_name: Option<String>,
+    #[clap(long, help = "Unit of the user defined metric")]
+    metric_unit: Option<String>,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "neutral",
+        help = "Which way the user defined metric is better"
+    )]
+    metric_direction: Direction,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "run",
+        help = "Run the metric script after each run, once after each pair of runs reading \
+            a cumulative counter whose increment is split by wall time, or concurrently with \
+            each run with ABSH_RUN_PID set; --metric-file only supports run"
+    )]
+    metric_scope: MetricScope,
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "0",
+        help = "Subtract this from each metric sample (clamped at zero)"
+    )]
+    metric_offset: u64,
+    #[clap(
+        long,
+        value_name = "FACTOR",
+        requires = "metric_source",
+        value_parser = parse_sanity_factor,
+        help = "Warn when a metric sample is more than FACTOR times above or below \
+            the running median (the sample is still recorded)"
+    )]
+    metric_sanity: Option<f64>,
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = parse_at_least_one,
+        help = "Each run does N units of work, e.g. requests; also report throughput \
+            in units per second, higher is better"
+    )]
+    work_units: Option<u32>,
+    #[clap(
+        long,
+        value_name = "DURATION",
+        default_value = "0",
+        help = "Subtract this from each measured time (clamped at zero), e.g. `5ms`"
+    )]
+    time_offset: Duration,
+    #[clap(
+        long,
+        help = "Run scripts with only PATH and a few other essential environment variables"
+    )]
+    isolate: bool,
+    #[clap(
+        long,
+        help = "Do not check before starting that scripts parse and their first command exists"
+    )]
+    no_preflight: bool,
+    #[clap(
+        long,
+        value_name = "KEY=VALUE",
+        value_parser = parse_env,
+        help = "Set environment variable for scripts, can be repeated"
+    )]
+    env: Vec<(String, String)>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Connect the file to stdin of each measured run (stdin is null by default)"
+    )]
+    stdin_file: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "Capture stdout and stderr of warmup and run scripts instead of printing them"
+    )]
+    capture: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "20",
+        help = "With --capture, print the last N lines of output of a failed script"
+    )]
+    fail_output_lines: usize,
+    #[clap(
+        long,
+        help = "Treat problems which are warnings by default as errors, \
+            and abort on the first script failure with exit code 2"
+    )]
+    strict: bool,
+    #[clap(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_positive_duration,
+        help = "Stop after this much time, e.g. `90s`, `30m` or `2h` (run forever if not specified)"
+    )]
+    max_duration: Option<Duration>,
+    #[clap(
+        long,
+        default_value = "0",
+        help = "Retry failed script up to this many times before giving up on the iteration"
+    )]
+    retries: u32,
+    #[clap(
+        long,
+        help = "Keep going even if an experiment has never succeeded after several attempts"
+    )]
+    keep_going: bool,
+    #[clap(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_positive_duration,
+        help = "Kill the script if it runs longer than this, e.g. `30s`"
+    )]
+    timeout: Option<Duration>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with = "load",
+        help = "After the script returns, wait until this file exists and include the wait \
+            in wall time; the file is removed before each run"
+    )]
+    wait_for: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "SIGNAL",
+        default_value = "KILL",
+        help = "Signal sent to the script process group on timeout"
+    )]
+    timeout_signal: Signal,
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        default_value = "5",
+        help = "Time to wait after --timeout-signal before sending SIGKILL"
+    )]
+    timeout_grace: Duration,
+    #[clap(
+        long,
+        value_name = "SCRIPT",
+        requires = "metric_source",
+        conflicts_with = "timeout",
+        help = "Treat variant scripts as servers: start each, run this load script against it \
+            for --window, record --metric (e.g. requests/sec), then stop the server"
+    )]
+    load: Option<String>,
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        default_value = "10",
+        value_parser = parse_positive_duration,
+        help = "How long the --load script runs against each server"
+    )]
+    window: Duration,
+    #[clap(
+        long,
+        value_enum,
+        help = "Display times in this unit (by default each number is scaled)"
+    )]
+    time_unit: Option<TimeUnit>,
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..=17),
+        help = "Display wall times with N significant figures instead of three decimal places, \
+            other measures are integers and unaffected"
+    )]
+    sigfigs: Option<u32>,
+    #[clap(
+        long,
+        help = "Display RSS and metric stats without thousands separators"
+    )]
+    no_thousands: bool,
+    #[clap(long, help = "Also write distribution plots to the log file")]
+    log_full_graph: bool,
+    #[clap(
+        long,
+        help = "Write raw files with one sample per line: value, start time in seconds since \
+            Unix epoch and experiment"
+    )]
+    raw_timestamps: bool,
+    #[clap(
+        long,
+        requires = "raw_timestamps",
+        help = "Use --name-a etc. labels instead of letters in raw files"
+    )]
+    raw_labels: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["html", "prometheus", "tap", "oneline", "target", "fail_if_faster",
+            "fail_if_worse", "score"],
+        help = "Only collect samples and write raw files when finished, without computing or \
+            printing stats"
+    )]
+    raw_only: bool,
+    #[clap(
+        long,
+        help = "Do not print distribution plots to stderr (see --log-full-graph for the log file)"
+    )]
+    no_plots: bool,
+    #[clap(
+        long,
+        help = "Do not write histograms to graph.svg in the run log directory"
+    )]
+    no_graph_svg: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "1",
+        value_parser = parse_at_least_one,
+        help = "Print stats every N iterations (final stats are always printed)"
+    )]
+    report_every: u32,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write self-contained HTML report with stats and histograms"
+    )]
+    html: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write stats in Prometheus text format, e.g. for node_exporter textfile collector"
+    )]
+    prometheus: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "MEASURES",
+        value_delimiter = ',',
+        help = "Only include these measures in --html, --prometheus and report.md, e.g. \
+            `time,rss` (all by default)"
+    )]
+    export_measures: Vec<MeasureKey>,
+    #[clap(
+        long,
+        help = "Print TAP report to stdout when finished, failing experiments worse than the reference"
+    )]
+    tap: bool,
+    #[clap(
+        long,
+        value_name = "OTHER/BASE",
+        num_args = 0..=1,
+        default_missing_value = "B/A",
+        value_parser = parse_comparison,
+        help = "Print a one line summary to stdout when finished, e.g. \
+            `absh: B 12.3% faster than A (p=0.002, n=200)`; compares B with A by default"
+    )]
+    oneline: Option<(ExperimentName, ExperimentName)>,
+    #[clap(
+        long,
+        value_name = "NAME",
+        default_value = "A",
+        value_parser = parse_experiment_name,
+        help = "Experiment other experiments are compared with, e.g. in ratios, \
+            --fail-if-faster and --tap"
+    )]
+    reference: ExperimentName,
+    #[clap(
+        long,
+        value_name = "MEASURE[.STAT]=VALUE",
+        help = "Check every experiment against an absolute threshold when finished, \
+            e.g. `time.p99=200ms`, `rss=512MiB` or `metric.mean=1000`, can be repeated \
+            (exit code 3 if not met)"
+    )]
+    target: Vec<Target>,
+    #[clap(
+        long,
+        value_name = "PERCENT",
+        value_parser = parse_non_negative,
+        help = "Exit with code 3 if an experiment is significantly faster than the reference by more than \
+            PERCENT, which often means the benchmark broke"
+    )]
+    fail_if_faster: Option<f64>,
+    #[clap(
+        long,
+        value_name = "MEASURE:PERCENT",
+        help = "Exit with code 3 if an experiment is significantly worse than the reference by more than \
+            PERCENT in the measure, e.g. `time:5%` or `max-rss:10%`, can be repeated"
+    )]
+    fail_if_worse: Vec<WorseLimit>,
+    #[clap(
+        long,
+        value_name = "MEASURE:WEIGHT,...",
+        help = "Rank experiments by the weighted sum of their mean ratios to the reference, \
+            inverted where higher is better, e.g. `wall-time:0.7,max-rss:0.3`"
+    )]
+    score: Option<ScoreWeights>,
+    #[clap(
+        long,
+        help = "Before each run wait until load average and temperature drop below thresholds"
+    )]
+    cool_to_idle: bool,
+    #[clap(
+        long,
+        value_name = "LOAD",
+        default_value = "1.0",
+        value_parser = parse_non_negative,
+        help = "One minute load average considered idle for --cool-to-idle"
+    )]
+    idle_load: f64,
+    #[clap(
+        long,
+        value_name = "CELSIUS",
+        default_value = "60",
+        help = "Max thermal zone temperature considered idle for --cool-to-idle (Linux only)"
+    )]
+    idle_temp: f64,
+    #[clap(
+        long,
+        value_name = "DURATION",
+        default_value = "60s",
+        help = "Run anyway if not idle after this time with --cool-to-idle"
+    )]
+    idle_max_wait: Duration,
+    #[clap(
+        long,
+        help = "Exclude samples further than 3 sigma from the mean from stats and plots"
+    )]
+    drop_outliers: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["drop_outliers", "rolling_mean"],
+        help = "Bounded memory for long sessions: keep a random sample of 10000 values per \
+            measure for median, plots and raw files; other stats are of all samples"
+    )]
+    streaming: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = parse_at_least_one,
+        conflicts_with_all = ["drop_outliers", "rolling_mean", "streaming"],
+        help = "Like --streaming, but keep N values per measure; samples are exact until \
+            an experiment has more than N"
+    )]
+    max_samples_stored: Option<u32>,
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "10",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(2..),
+        help = "Only drop outliers once an experiment has at least N samples"
+    )]
+    outlier_min_n: usize,
+    #[clap(
+        long,
+        value_name = "PERCENT",
+        default_value = "2",
+        value_parser = parse_non_negative,
+        help = "Coefficient of variation at which std is shown yellow"
+    )]
+    cv_warn: f64,
+    #[clap(
+        long,
+        value_name = "PERCENT",
+        default_value = "10",
+        value_parser = parse_non_negative,
+        help = "Coefficient of variation at which std is shown red"
+    )]
+    cv_bad: f64,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "name",
+        help = "Order of experiments in displayed stats (logged raw data keeps A-E order)"
+    )]
+    sort: SortKey,
+    #[clap(long, help = "Sort experiments in descending order")]
+    sort_desc: bool,
+    #[clap(
+        long,
+        value_name = "ALPHA",
+        default_value = "0.05",
+        value_parser = parse_alpha,
+        help = "Significance level for reporting the best experiment rather than a tie"
+    )]
+    alpha: f64,
+    #[clap(
+        long,
+        value_name = "K",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        help = "Also show the mean of the last K samples, it agrees with the overall mean \
+            once results converge"
+    )]
+    rolling_mean: Option<usize>,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "plain",
+        help = "How to draw the stats table, `box` adds a header and borders"
+    )]
+    table_style: TableStyle,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "default",
+        help = "Colors for good and bad results, `color-blind` uses blue and orange, \
+            both it and `mono` mark better and worse comparisons with ▲ and ▼"
+    )]
+    palette: Palette,
+    #[clap(
+        long,
+        help = "Scale distribution plots to the fraction of each experiment's samples \
+            rather than counts, to compare shapes when sample counts differ"
+    )]
+    normalize_hist: bool,
+    #[clap(
+        long,
+        help = "Bin distribution plots by the log of the value, to show structure of samples \
+            spanning orders of magnitude"
+    )]
+    log_scale: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = parse_progress_fd,
+        help = "After each run write a JSON progress line to file descriptor N (3 or above), \
+            e.g. for GUI wrappers"
+    )]
+    progress_fd: Option<i32>,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "auto",
+        help = "Colorize stderr (auto: if stderr is a terminal and NO_COLOR is not set)"
+    )]
+    color: ColorChoice,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Log format, json also writes events.jsonl to the run log directory with \
+            a JSON line for each run start, result, failure and stats render"
+    )]
+    log_format: LogFormat,
+    #[clap(
+        short = 'v',
+        long = "verbose",
+        action = clap::ArgAction::Count,
+        conflicts_with = "quiet",
+        help = "Also print the working directory and command line of each run, \
+            and with -vv each sample as it is recorded"
+    )]
+    verbose: u8,
+    #[clap(
+        short = 'q',
+        long,
+        help = "Only print final stats, warnings and errors (the log still has progress)"
+    )]
+    quiet: bool,
 }
 
-fn run_test(log: &mut RunLog, test: &mut Experiment) -> anyhow::Result<()> {
-    writeln!(log.both_log_and_stderr())?;
-    writeln!(
-        log.both_log_and_stderr(),
-        "running test: {}",
-        test.name.name_colored()
-    )?;
-    let warmup_lines = test.warmup.lines().collect::<Vec<_>>();
-    if !warmup_lines.is_empty() {
-        writeln!(log.both_log_and_stderr(), "running warmup script:")?;
-        for line in &warmup_lines {
-            writeln!(log.both_log_and_stderr(), "    {}", line)?;
-        }
+/// Exit code when a script failed, other errors exit with 1.
+const SCRIPT_FAILED_EXIT_CODE: i32 = 2;
+/// Exit code when a `--target` or `--fail-if-faster` check fails.
+const CHECK_FAILED_EXIT_CODE: i32 = 3;
+
+fn parse_script(s: &str) -> anyhow::Result<String> {
+    if s.trim().is_empty() {
+        return Err(anyhow::anyhow!("script must not be empty"));
     }
+    Ok(s.to_owned())
+}
 
-    let mut process = spawn_sh(&test.warmup)?;
-    let status = process.wait4()?;
-    if !status.status.success() {
-        writeln!(
-            log.both_log_and_stderr(),
-            "warmup failed: {}",
-            status.status
-        )?;
-        return Ok(());
+fn parse_label(s: &str) -> anyhow::Result<String> {
+    if s.is_empty() || s.contains(char::is_whitespace) {
+        return Err(anyhow::anyhow!(
+            "label must be non-empty without whitespace"
+        ));
     }
+    Ok(s.to_owned())
+}
 
-    writeln!(log.both_log_and_stderr(), "running script:")?;
-    let lines = test.run.lines().collect::<Vec<_>>();
-    for line in &lines {
-        writeln!(log.both_log_and_stderr(), "    {}", line)?;
+fn parse_at_least_one(s: &str) -> anyhow::Result<u32> {
+    match s.parse::<u32>()? {
+        0 => Err(anyhow::anyhow!("must be at least 1")),
+        n => Ok(n),
     }
+}
 
-    let start = Instant::now();
+fn parse_progress_fd(s: &str) -> anyhow::Result<i32> {
+    match s.parse::<i32>()? {
+        // Progress would mix with absh output, and the fd is closed when dropped.
+        n if n <= 2 => Err(anyhow::anyhow!(
+            "must be at least 3, 0-2 are stdin, stdout and stderr"
+        )),
+        n => Ok(n),
+    }
+}
 
-    let mut process = spawn_sh(&test.run)?;
-    let status = process.wait4()?;
-    if !status.status.success() {
-        writeln!(
-            log.both_log_and_stderr(),
-            "script failed: {}",
-            status.status
-        )?;
-        return Ok(());
+fn parse_positive_duration(s: &str) -> anyhow::Result<Duration> {
+    let duration: Duration = s.parse()?;
+    if duration == Duration::default() {
+        return Err(anyhow::anyhow!("must be greater than zero"));
     }
+    Ok(duration)
+}
 
-    let duration = Duration::from_nanos(start.elapsed().as_nanos().try_into()?);
-    if status.rusage.maxrss == 0 {
-        return Err(anyhow::anyhow!("maxrss not available"));
+fn parse_non_negative(s: &str) -> anyhow::Result<f64> {
+    let value: f64 = s.parse()?;
+    if value.is_nan() || value < 0.0 {
+        return Err(anyhow::anyhow!("must not be negative"));
     }
-    let max_rss = MemUsage::from_bytes(status.rusage.maxrss);
+    Ok(value)
+}
 
-    writeln!(
-        log.both_log_and_stderr(),
-        "{} finished in {:3} s, max rss {} MiB",
-        test.name.name_colored(),
-        duration,
-        max_rss.mib(),
-    )?;
+fn parse_positive_percent(s: &str) -> anyhow::Result<f64> {
+    let percent: f64 = s.parse()?;
+    if !percent.is_finite() || percent <= 0.0 {
+        return Err(anyhow::anyhow!("must be positive"));
+    }
+    Ok(percent)
+}
 
-    test.measures[MeasureKey::WallTime].push(duration.nanos());
-    test.measures[MeasureKey::MaxRss].push(max_rss.bytes());
-    Ok(())
+fn parse_sanity_factor(s: &str) -> anyhow::Result<f64> {
+    let factor: f64 = s.parse()?;
+    if factor.is_nan() || factor <= 1.0 {
+        return Err(anyhow::anyhow!("must be greater than 1"));
+    }
+    Ok(factor)
 }
 
-fn run_pair(
-    log: &mut RunLog,
-    opts: &Opts,
-    tests: &mut ExperimentMap<Experiment>,
-) -> anyhow::Result<()> {
-    let mut indices: Vec<ExperimentName> = tests.keys().collect();
-    if opts.random_order {
-        indices.shuffle(&mut rand::thread_rng());
+fn parse_alpha(s: &str) -> anyhow::Result<f64> {
+    let alpha: f64 = s.parse()?;
+    if !(alpha > 0.0 && alpha < 1.0) {
+        return Err(anyhow::anyhow!("must be between 0 and 1"));
     }
-    for &index in &indices {
-        run_test(log, tests.get_mut(index).unwrap())?;
+    Ok(alpha)
+}
+
+fn parse_experiment_name(s: &str) -> anyhow::Result<ExperimentName> {
+    ExperimentName::from_name(&s.to_uppercase())
+        .ok_or_else(|| anyhow::anyhow!("expecting experiment name A to E: {:?}", s))
+}
+
+fn parse_comparison(s: &str) -> anyhow::Result<(ExperimentName, ExperimentName)> {
+    let (other, base) = s
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("expecting two experiments like B/A: {:?}", s))?;
+    let (other, base) = (parse_experiment_name(other)?, parse_experiment_name(base)?);
+    if other == base {
+        return Err(anyhow::anyhow!(
+            "expecting two different experiments: {:?}",
+            s
+        ));
     }
-    Ok(())
+    Ok((other, base))
 }
 
-fn main() -> anyhow::Result<()> {
-    let opts: Opts = Opts::parse();
+fn parse_env(s: &str) -> anyhow::Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => Err(anyhow::anyhow!("expecting KEY=VALUE: {:?}", s)),
+    }
+}
 
-    let mut log = RunLog::open();
+/// Progress line written to `--progress-fd`.
+fn progress_json(run: &CompletedRun) -> String {
+    format!(
+        "{{\"experiment\":\"{}\",\"iteration\":{},\"wall_time_ns\":{},\"mean_wall_time_ns\":{}}}",
+        run.experiment,
+        run.iteration,
+        run.wall_time.nanos(),
+        run.mean_wall_time.nanos(),
+    )
+}
 
-    let mut experiments = ExperimentMap::default();
-    experiments.insert(
-        ExperimentName::A,
-        Experiment {
-            name: ExperimentName::A,
-            warmup: opts.aw.clone().unwrap_or(String::new()),
-            run: opts.a.clone(),
-            measures: MeasureMap::new_all_default(),
-        },
-    );
+#[cfg(unix)]
+fn progress_writer(fd: i32) -> anyhow::Result<OnRun> {
+    use std::os::unix::io::FromRawFd;
 
-    fn parse_opt_test(
-        tests: &mut ExperimentMap<Experiment>,
-        name: ExperimentName,
-        run: &Option<String>,
-        warmup: &Option<String>,
-    ) {
-        if let Some(run) = run.clone() {
-            tests.insert(
-                name,
-                Experiment {
-                    name,
-                    warmup: warmup.clone().unwrap_or(String::new()),
-                    run,
-                    measures: MeasureMap::new_all_default(),
-                },
-            );
-        }
+    // Scripts should not inherit the fd, so the wrapper sees EOF when absh exits.
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+        return Err(anyhow::anyhow!("--progress-fd {} is not open", fd));
     }
-    parse_opt_test(&mut experiments, ExperimentName::B, &opts.b, &opts.bw);
-    parse_opt_test(&mut experiments, ExperimentName::C, &opts.c, &opts.cw);
-    parse_opt_test(&mut experiments, ExperimentName::D, &opts.d, &opts.dw);
-    parse_opt_test(&mut experiments, ExperimentName::E, &opts.e, &opts.ew);
+    let file = unsafe { File::from_raw_fd(fd) };
+    Ok(Box::new(move |run| {
+        // Wrapper may have closed the fd, which should not stop the benchmark.
+        let _ = writeln!(&file, "{}", progress_json(run));
+    }))
+}
 
-    eprintln!("Writing absh data to {}/", log.name().display());
-    if let Some(last) = log.last() {
-        eprintln!("Log symlink is {}", last.display());
-    }
+#[cfg(not(unix))]
+fn progress_writer(_fd: i32) -> anyhow::Result<OnRun> {
+    Err(anyhow::anyhow!(
+        "--progress-fd is not supported on this platform"
+    ))
+}
 
-    log.write_args()?;
+impl Opts {
+    /// Replace script `-` with the content of stdin.
+    fn read_stdin_script(&mut self) -> anyhow::Result<()> {
+        let mut scripts: Vec<&mut String> = vec![&mut self.a];
+        scripts.extend(
+            vec![
+                &mut self.b,
+                &mut self.c,
+                &mut self.d,
+                &mut self.e,
+                &mut self.aw,
+                &mut self.bw,
+                &mut self.cw,
+                &mut self.dw,
+                &mut self.ew,
+                &mut self.global_warmup,
+                &mut self.between,
+            ]
+            .into_iter()
+            .flatten(),
+        );
+        let mut stdin_scripts: Vec<&mut String> =
+            scripts.into_iter().filter(|s| s.as_str() == "-").collect();
+        match stdin_scripts.as_mut_slice() {
+            [] => Ok(()),
+            [script] => {
+                let mut content = String::new();
+                io::stdin().read_to_string(&mut content)?;
+                **script = content;
+                Ok(())
+            }
+            _ => Err(anyhow::anyhow!(
+                "only one script can be read from stdin (`-`)"
+            )),
+        }
+    }
 
-    writeln!(log.log_only(), "random_order: {}", opts.random_order)?;
-    for (n, t) in experiments.iter_mut() {
-        writeln!(log.log_only(), "{}.run: {}", n, t.run)?;
-        if !t.warmup.is_empty() {
-            writeln!(log.log_only(), "{}.warmup: {}", n, t.warmup)?;
+    fn ignore_first(&self) -> u32 {
+        match self.ignore_first {
+            Some(k) => k,
+            None if self.ignore_first_one => 1,
+            None => 0,
         }
     }
 
-    if opts.ignore_first {
-        run_pair(&mut log, &opts, &mut experiments)?;
+    fn into_config(self) -> anyhow::Result<RunConfig> {
+        let ignore_first = self.ignore_first();
+        let on_run = self.progress_fd.map(progress_writer).transpose()?;
+        Ok(RunConfig {
+            a: self.a,
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            aw: self.aw,
+            bw: self.bw,
+            cw: self.cw,
+            dw: self.dw,
+            ew: self.ew,
+            name_a: self.name_a,
+            name_b: self.name_b,
+            name_c: self.name_c,
+            name_d: self.name_d,
+            name_e: self.name_e,
+            warmup_runs: self.warmup_runs,
+            warmup_time: self.warmup_time,
+            global_warmup: self.global_warmup,
+            warmup_only: self.warmup_only,
+            between: self.between,
+            random_order: self.random_order,
+            replay_order: self.replay_order,
+            order: self.order,
+            ignore_first,
+            iterations: self.iterations,
+            min_iterations: self.min_iterations,
+            prompt: !self.no_prompt,
+            auto: self.auto,
+            inner_runs: [
+                self.inner_a,
+                self.inner_b,
+                self.inner_c,
+                self.inner_d,
+                self.inner_e,
+            ],
+            mem: self.mem,
+            mem_experiments: [
+                (ExperimentName::A, self.mem_a),
+                (ExperimentName::B, self.mem_b),
+                (ExperimentName::C, self.mem_c),
+                (ExperimentName::D, self.mem_d),
+                (ExperimentName::E, self.mem_e),
+            ]
+            .iter()
+            .filter(|(_, mem)| *mem)
+            .map(|(name, _)| *name)
+            .collect(),
+            avg_rss: self.avg_rss,
+            metric: self.metric,
+            metric_name: self.metric_name,
+            metric_unit: self.metric_unit,
+            metric_direction: self.metric_direction,
+            metric_scope: self.metric_scope,
+            metric_file: self.metric_file,
+            metric_file_delete: self.metric_file_delete,
+            metric_offset: self.metric_offset,
+            metric_sanity: self.metric_sanity,
+            work_units: self.work_units.map(u64::from),
+            time_offset: self.time_offset,
+            isolate: self.isolate,
+            preflight: !self.no_preflight,
+            env: self.env,
+            stdin_file: self.stdin_file,
+            capture: self.capture,
+            fail_output_lines: self.fail_output_lines,
+            strict: self.strict,
+            max_duration: self.max_duration,
+            retries: self.retries,
+            keep_going: self.keep_going,
+            timeout: self.timeout,
+            wait_for: self.wait_for,
+            timeout_signal: self.timeout_signal,
+            timeout_grace: self.timeout_grace,
+            load: self.load,
+            window: self.window,
+            time_unit: self.time_unit,
+            sigfigs: self.sigfigs.map(|n| n as usize),
+            no_thousands: self.no_thousands,
+            log_full_graph: self.log_full_graph,
+            raw_timestamps: self.raw_timestamps,
+            raw_labels: self.raw_labels,
+            raw_only: self.raw_only,
+            no_plots: self.no_plots,
+            graph_svg: !self.no_graph_svg,
+            report_every: self.report_every,
+            html: self.html,
+            prometheus: self.prometheus,
+            export_measures: self.export_measures,
+            tap: self.tap,
+            oneline: self.oneline,
+            reference: self.reference,
+            targets: self.target,
+            fail_if_faster: self.fail_if_faster,
+            fail_if_worse: self.fail_if_worse,
+            score: self.score,
+            cool_to_idle: self.cool_to_idle,
+            idle_load: self.idle_load,
+            idle_temp: self.idle_temp,
+            idle_max_wait: self.idle_max_wait,
+            drop_outliers: self.drop_outliers,
+            max_samples_stored: match (self.max_samples_stored, self.streaming) {
+                (Some(n), _) => Some(n as usize),
+                (None, true) => Some(STREAMING_RESERVOIR_SIZE),
+                (None, false) => None,
+            },
+            outlier_min_n: self.outlier_min_n,
+            cv_warn: self.cv_warn,
+            cv_bad: self.cv_bad,
+            sort: self.sort,
+            sort_desc: self.sort_desc,
+            alpha: self.alpha,
+            rolling_mean: self.rolling_mean,
+            table_style: self.table_style,
+            palette: self.palette,
+            normalize_hist: self.normalize_hist,
+            log_scale: self.log_scale,
+            color: self.color,
+            log_format: self.log_format,
+            verbosity: Verbosity::from_flags(self.quiet, self.verbose),
+            on_run,
+        })
+    }
+}
 
-        for (_n, test) in experiments.iter_mut() {
-            for numbers in test.measures.values_mut() {
-                numbers.clear();
-            }
-        }
+/// `absh merge` arguments.
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "absh merge",
+    about = "Combine samples of several run directories, e.g. from different hosts, and print stats"
+)]
+struct MergeOpts {
+    #[clap(required = true, help = "Run directories, e.g. ~/.absh/logs/last")]
+    dirs: Vec<PathBuf>,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "auto",
+        help = "Colorize stderr (auto: if stderr is a terminal and NO_COLOR is not set)"
+    )]
+    color: ColorChoice,
+}
 
-        writeln!(log.both_log_and_stderr(), "")?;
-        writeln!(
-            log.both_log_and_stderr(),
-            "Ignoring first run pair results."
-        )?;
-        writeln!(log.both_log_and_stderr(), "Now collecting the results.")?;
-        writeln!(
-            log.both_log_and_stderr(),
-            "Statistics will be printed after the second successful iteration."
-        )?;
-    } else {
-        writeln!(log.both_log_and_stderr(), "")?;
+fn merge_main(opts: MergeOpts) -> anyhow::Result<()> {
+    let merged = merge(&opts.dirs)?;
+    let mut stderr = ConsoleWriter::new(opts.color);
+    for warning in &merged.warnings {
         writeln!(
-            log.both_log_and_stderr(),
-            "{yellow}First run pair results will be used in statistics.{reset}",
-            yellow = ansi::YELLOW,
-            reset = ansi::RESET,
-        )?;
-        writeln!(
-            log.both_log_and_stderr(),
-            "{yellow}Results might be skewed.{reset}",
-            yellow = ansi::YELLOW,
-            reset = ansi::RESET,
-        )?;
-        writeln!(
-            log.both_log_and_stderr(),
-            "{yellow}Use `-i` command line flag to ignore the first iteration.{reset}",
+            stderr,
+            "{yellow}{}{reset}",
+            warning,
             yellow = ansi::YELLOW,
             reset = ansi::RESET,
         )?;
     }
-
-    let mut measures: Vec<Box<dyn MeasureDyn>> = Vec::new();
-    measures.push(Box::new(WallTime));
-    if opts.mem {
-        measures.push(Box::new(MaxRss));
+    let summary = SummaryReport::new(&merged.measures, &merged.experiments, ExperimentName::A);
+    if summary.measures.is_empty() {
+        return Err(anyhow::anyhow!("not enough samples to compute stats"));
     }
-    let measures = AllMeasures(measures);
-
-    loop {
-        run_pair(&mut log, &opts, &mut experiments)?;
-
-        let min_count = experiments.values_mut().map(|t| t.runs()).min().unwrap();
-        if Some(min_count) == opts.iterations.map(|n| n as usize) {
-            break;
-        }
+    write!(
+        stderr,
+        "{}",
+        merged
+            .measures
+            .render_stats(&merged.experiments, &summary, &RenderOptions::default())?
+    )?;
+    Ok(())
+}
 
-        if min_count < 2 {
-            continue;
+fn main() -> anyhow::Result<()> {
+    if std::env::args().nth(1).as_deref() == Some("merge") {
+        return merge_main(MergeOpts::parse_from(std::env::args().skip(1)));
+    }
+    let mut opts: Opts = Opts::parse();
+    opts.read_stdin_script()?;
+    match run(opts.into_config()?) {
+        Ok(results) if !results.checks_passed => process::exit(CHECK_FAILED_EXIT_CODE),
+        Ok(_) => Ok(()),
+        Err(e) if e.is::<ScriptFailed>() => {
+            eprintln!("Error: {}", e);
+            process::exit(SCRIPT_FAILED_EXIT_CODE);
         }
-
-        writeln!(log.both_log_and_stderr(), "")?;
-
-        let graph_full = measures.render_stats(&experiments, true)?;
-        let graph_short = measures.render_stats(&experiments, false)?;
-
-        write!(log.stderr_only(), "{}", graph_full)?;
-        write!(log.log_only(), "{}", graph_short,)?;
-
-        log.write_graph(&graph_full)?;
-
-        measures.write_raw(&experiments, &mut log)?;
+        Err(e) => Err(e),
     }
-
-    Ok(())
 }
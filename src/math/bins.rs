@@ -0,0 +1,38 @@
+/// Count samples in `bins` bins spanning `range` inclusive, shared by all plots.
+///
+/// Samples outside the range are counted in the nearest edge bin,
+/// and all samples are in the first bin if the range is a single value.
+pub fn bin_samples(samples: &[u64], bins: usize, range: (u64, u64)) -> Vec<usize> {
+    let mut counts = vec![0; bins];
+    if bins == 0 {
+        return counts;
+    }
+    let (min, max) = range;
+    for &sample in samples {
+        let bin = if min >= max {
+            0
+        } else {
+            let sample = sample.clamp(min, max);
+            // Edge bins are half as wide, so min and max are centered in them.
+            (((sample - min) as f64) / ((max - min) as f64) * ((bins - 1) as f64)).round() as usize
+        };
+        counts[bin.min(bins - 1)] += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod test {
+    use crate::math::bins::bin_samples;
+
+    #[test]
+    fn bins() {
+        assert_eq!(vec![0, 0, 0], bin_samples(&[], 3, (0, 10)));
+        assert_eq!(Vec::<usize>::new(), bin_samples(&[1, 2], 0, (0, 10)));
+        assert_eq!(vec![3, 0], bin_samples(&[7, 7, 7], 2, (7, 7)));
+        assert_eq!(vec![1], bin_samples(&[5], 1, (0, 10)));
+        assert_eq!(vec![2, 1, 2], bin_samples(&[0, 2, 5, 9, 10], 3, (0, 10)));
+        // Out of range samples are clamped.
+        assert_eq!(vec![1, 0, 1], bin_samples(&[1, 20], 3, (5, 10)));
+    }
+}
@@ -0,0 +1,38 @@
+/// Pearson correlation coefficient of paired samples.
+///
+/// `None` with fewer than 3 pairs or when either side is constant.
+pub fn pearson(xs: &[u64], ys: &[u64]) -> Option<f64> {
+    let n = usize::min(xs.len(), ys.len());
+    if n < 3 {
+        return None;
+    }
+    let (xs, ys) = (&xs[..n], &ys[..n]);
+    let mean = |v: &[u64]| v.iter().map(|&x| x as f64).sum::<f64>() / n as f64;
+    let (mx, my) = (mean(xs), mean(ys));
+    let mut sxy = 0.0;
+    let mut sxx = 0.0;
+    let mut syy = 0.0;
+    for (&x, &y) in xs.iter().zip(ys) {
+        let (dx, dy) = (x as f64 - mx, y as f64 - my);
+        sxy += dx * dy;
+        sxx += dx * dx;
+        syy += dy * dy;
+    }
+    if sxx == 0.0 || syy == 0.0 {
+        return None;
+    }
+    Some(sxy / (sxx * syy).sqrt())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::math::correlation::pearson;
+
+    #[test]
+    fn pearson_signs() {
+        assert_eq!(Some(1.0), pearson(&[1, 2, 3, 4], &[10, 20, 30, 40]));
+        assert_eq!(Some(-1.0), pearson(&[1, 2, 3], &[30, 20, 10]));
+        assert_eq!(None, pearson(&[1, 1, 1], &[1, 2, 3]));
+        assert_eq!(None, pearson(&[1, 2], &[1, 2]));
+    }
+}
@@ -1,3 +1,8 @@
+pub mod bins;
+pub mod correlation;
 pub mod numbers;
+pub mod sigfigs;
 pub mod sorted;
 pub mod stats;
+pub mod thousands;
+pub mod welford;
@@ -1,6 +1,10 @@
+use rand::Rng;
+
+use crate::math::bins::bin_samples;
 use crate::math::sorted::NumbersSorted;
-use crate::math::stats::stats;
 use crate::math::stats::Stats;
+use crate::math::stats::stats;
+use crate::math::welford::Welford;
 
 pub struct Distr {
     pub counts: Vec<u64>,
@@ -16,30 +20,113 @@ impl Distr {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Numbers {
     raw: Vec<u64>,
     sorted: Vec<u64>,
+    /// Set in streaming mode, where `raw` holds a uniform random sample of all values.
+    streaming: Option<Streaming>,
+}
+
+#[derive(Clone)]
+struct Streaming {
+    /// Maximum length of `raw`.
+    capacity: usize,
+    all: Welford,
+    last: Option<u64>,
 }
 
 impl Numbers {
-    pub fn push(&mut self, d: u64) {
-        self.raw.push(d.clone());
+    /// Keep at most `capacity` samples, chosen by reservoir sampling.
+    ///
+    /// Count, sum, min, max, mean and std are of all samples, median and
+    /// percentiles are of the kept ones. Kept samples are not in run order.
+    pub fn streaming(capacity: usize) -> Numbers {
+        assert!(capacity >= 2);
+        Numbers {
+            streaming: Some(Streaming {
+                capacity,
+                all: Welford::default(),
+                last: None,
+            }),
+            ..Numbers::default()
+        }
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        self.streaming.is_some()
+    }
+
+    pub fn from_slice(numbers: &[u64]) -> Numbers {
+        let mut r = Numbers::default();
+        for &n in numbers {
+            r.push(n);
+        }
+        r
+    }
+
+    /// Add a sample, returning its index in `raw`, or `None` if it is not kept.
+    pub fn push(&mut self, d: u64) -> Option<usize> {
+        let index = match &mut self.streaming {
+            None => self.raw.len(),
+            Some(streaming) => {
+                streaming.all.push(d);
+                streaming.last = Some(d);
+                if self.raw.len() < streaming.capacity {
+                    self.raw.len()
+                } else {
+                    // Algorithm R: keep each of n samples with probability capacity/n.
+                    let index = rand::thread_rng().gen_range(0, streaming.all.count()) as usize;
+                    if index >= streaming.capacity {
+                        return None;
+                    }
+                    let replaced = self.sorted.binary_search(&self.raw[index]).unwrap();
+                    self.sorted.remove(replaced);
+                    index
+                }
+            }
+        };
+        if index == self.raw.len() {
+            self.raw.push(d);
+        } else {
+            self.raw[index] = d;
+        }
         let idx = self.sorted.binary_search(&d).unwrap_or_else(|x| x);
         self.sorted.insert(idx, d);
+        Some(index)
     }
 
     pub fn clear(&mut self) {
         self.raw.clear();
         self.sorted.clear();
+        if let Some(streaming) = &mut self.streaming {
+            streaming.all = Welford::default();
+            streaming.last = None;
+        }
     }
 
+    /// Samples in run order, or the kept ones in streaming mode.
     pub fn raw(&self) -> &[u64] {
         &self.raw
     }
 
+    /// Most recent sample.
+    pub fn last(&self) -> Option<u64> {
+        match &self.streaming {
+            Some(streaming) => streaming.last,
+            None => self.raw.last().copied(),
+        }
+    }
+
     pub fn len(&self) -> usize {
-        self.raw.len()
+        match &self.streaming {
+            Some(streaming) => streaming.all.count() as usize,
+            None => self.raw.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
     pub fn med(&self) -> Option<u64> {
@@ -47,50 +134,97 @@ impl Numbers {
     }
 
     pub fn min(&self) -> Option<u64> {
-        self.sorted().min()
+        match &self.streaming {
+            Some(streaming) => streaming.all.min(),
+            None => self.sorted().min(),
+        }
     }
 
     pub fn max(&self) -> Option<u64> {
-        self.sorted().max()
+        match &self.streaming {
+            Some(streaming) => streaming.all.max(),
+            None => self.sorted().max(),
+        }
     }
 
     pub fn sum(&self) -> u64 {
-        self.sorted().sum()
+        match &self.streaming {
+            Some(streaming) => streaming.all.sum(),
+            None => self.sorted().sum(),
+        }
     }
 
     pub fn mean(&self) -> Option<u64> {
-        self.sorted().mean()
+        match &self.streaming {
+            Some(streaming) => streaming.all.mean(),
+            None => self.sorted().mean(),
+        }
     }
 
     pub fn std(&self) -> Option<u64> {
-        self.sorted().std()
+        match &self.streaming {
+            Some(streaming) => streaming.all.std(),
+            None => self.sorted().std(),
+        }
+    }
+
+    /// Mean of the last `k` samples in run order.
+    pub fn tail_mean(&self, k: usize) -> Option<u64> {
+        let tail = &self.raw[self.raw.len().saturating_sub(k)..];
+        if tail.is_empty() {
+            return None;
+        }
+        Some((tail.iter().map(|&d| d as u128).sum::<u128>() / tail.len() as u128) as u64)
     }
 
     pub fn iter<'a>(&'a self) -> impl Iterator<Item = u64> + 'a {
         self.raw.iter().cloned()
     }
 
-    pub fn sorted(&self) -> NumbersSorted {
+    pub fn sorted(&self) -> NumbersSorted<'_> {
         NumbersSorted(&self.sorted)
     }
 
     pub fn distr(&self, n: usize, min: u64, max: u64) -> Distr {
-        let mut counts = vec![0; n];
-        if min != max {
-            for d in &self.raw {
-                let bucket = (((d.clone() - min.clone()) as f64)
-                    / ((max.clone() - min.clone()) as f64)
-                    * ((n - 1) as f64))
-                    .round() as usize;
-                counts[bucket.clamp(0, n - 1)] += 1;
-            }
+        Distr {
+            counts: bin_samples(&self.raw, n, (min, max))
+                .into_iter()
+                .map(|c| c as u64)
+                .collect(),
         }
-        Distr { counts }
     }
 
     pub fn stats(&self) -> Option<Stats<u64>> {
         stats(self)
     }
+
+    /// Split samples into those within 3 sigma of the mean and outliers, both in run order.
+    ///
+    /// Nothing is split in streaming mode, which does not keep all samples.
+    pub fn split_outliers(&self) -> (Numbers, Vec<u64>) {
+        if self.is_streaming() {
+            return (self.clone(), Vec::new());
+        }
+        let kept = self.sorted().filter_3_sigma();
+        let mut r = Numbers::default();
+        let mut outliers = Vec::new();
+        let range = match (kept.min(), kept.max()) {
+            (Some(min), Some(max)) => min..=max,
+            _ => return (r, outliers),
+        };
+        for n in self.iter() {
+            if range.contains(&n) {
+                r.push(n);
+            } else {
+                outliers.push(n);
+            }
+        }
+        (r, outliers)
+    }
+
+    pub fn without_outliers(&self) -> Numbers {
+        self.split_outliers().0
+    }
 }
 
 #[cfg(test)]
@@ -113,6 +247,52 @@ mod test {
         assert_eq!(10, ds.min().unwrap());
     }
 
+    #[test]
+    fn streaming() {
+        let mut ds = Numbers::streaming(10);
+        for d in 1..=1000 {
+            if let Some(index) = ds.push(d) {
+                assert_eq!(d, ds.raw()[index]);
+            }
+        }
+        assert_eq!(1000, ds.len());
+        assert_eq!(10, ds.raw().len());
+        assert_eq!(10, ds.sorted().len());
+        assert_eq!(Some(1), ds.min());
+        assert_eq!(Some(1000), ds.max());
+        assert_eq!(Some(500), ds.mean());
+        assert_eq!(Some(1000), ds.last());
+        ds.clear();
+        assert!(ds.is_empty());
+        assert!(ds.is_streaming());
+    }
+
+    #[test]
+    fn near_max() {
+        let ds = Numbers::from_slice(&[u64::MAX - 2, u64::MAX, u64::MAX - 1]);
+        let stats = ds.stats().unwrap();
+        assert_eq!(u64::MAX - 1, stats.mean);
+        assert_eq!(u64::MAX, stats.sum);
+        assert_eq!(u64::MAX - 1, stats.med);
+        assert_eq!(Some(u64::MAX - 1), ds.tail_mean(3));
+        assert_eq!(&[1, 1, 1], &ds.distr(3, u64::MAX - 2, u64::MAX).counts[..]);
+
+        let mut streaming = Numbers::streaming(2);
+        for &d in ds.raw() {
+            streaming.push(d);
+        }
+        assert_eq!(Some(u64::MAX - 1), streaming.mean());
+        assert_eq!(u64::MAX, streaming.sum());
+    }
+
+    #[test]
+    fn near_max_even_count() {
+        let ds = Numbers::from_slice(&[u64::MAX, u64::MAX - 1]);
+        let stats = ds.stats().unwrap();
+        assert_eq!(u64::MAX - 1, stats.med);
+        assert_eq!(u64::MAX - 1, stats.mean);
+    }
+
     #[test]
     fn distr_1() {
         let mut ds = Numbers::default();
@@ -151,6 +331,15 @@ mod test {
         assert_eq!(20, ds.mean().unwrap());
     }
 
+    #[test]
+    fn tail_mean() {
+        let ds = Numbers::from_slice(&[100, 10, 20, 30]);
+        assert_eq!(Some(25), ds.tail_mean(2));
+        assert_eq!(Some(40), ds.tail_mean(10));
+        assert_eq!(None, ds.tail_mean(0));
+        assert_eq!(None, Numbers::default().tail_mean(3));
+    }
+
     #[test]
     fn std() {
         let mut ds = Numbers::default();
@@ -160,4 +349,19 @@ mod test {
 
         assert_eq!(2, ds.std().unwrap())
     }
+
+    #[test]
+    fn without_outliers() {
+        let mut ds = Numbers::default();
+        for _ in 0..19 {
+            ds.push(10);
+        }
+        ds.push(1000);
+        ds.push(11);
+        let (trimmed, outliers) = ds.split_outliers();
+        assert_eq!(vec![1000], outliers);
+        assert_eq!(20, trimmed.len());
+        assert_eq!(Some(11), trimmed.raw().last().cloned());
+        assert_eq!(Some(11), trimmed.max());
+    }
 }
@@ -0,0 +1,29 @@
+/// Format number with given number of significant figures, e.g. `0.00312` or `3420`.
+pub fn format_sigfigs(value: f64, sigfigs: usize) -> String {
+    let sigfigs = sigfigs.max(1) as i32;
+    if value == 0.0 || !value.is_finite() {
+        return format!("{}", value);
+    }
+    let magnitude = value.abs().log10().floor() as i32;
+    let decimals = sigfigs - 1 - magnitude;
+    if decimals >= 0 {
+        format!("{:.*}", decimals as usize, value)
+    } else {
+        let scale = 10f64.powi(-decimals);
+        format!("{:.0}", (value / scale).round() * scale)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::math::sigfigs::format_sigfigs;
+
+    #[test]
+    fn sigfigs() {
+        assert_eq!("0.00312", format_sigfigs(0.0031234, 3));
+        assert_eq!("3420", format_sigfigs(3421.7, 3));
+        assert_eq!("3421.7", format_sigfigs(3421.7, 5));
+        assert_eq!("1.00", format_sigfigs(1.0, 3));
+        assert_eq!("0", format_sigfigs(0.0, 3));
+    }
+}
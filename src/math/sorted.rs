@@ -1,3 +1,5 @@
+use std::convert::TryFrom;
+
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
 pub struct NumbersSorted<'a>(pub &'a [u64]);
 
@@ -29,24 +31,40 @@ impl<'a> NumbersSorted<'a> {
         if self.is_empty() {
             None
         } else {
-            if self.len() % 2 == 0 {
-                let xy: u64 = self.0[self.len() / 2 - 1].clone() + self.0[self.len() / 2].clone();
-                Some(xy / 2)
+            if self.len().is_multiple_of(2) {
+                let (x, y) = (self.0[self.len() / 2 - 1], self.0[self.len() / 2]);
+                // `x + y` may overflow.
+                Some(x + (y - x) / 2)
             } else {
-                Some(self.0[self.len() / 2].clone())
+                Some(self.0[self.len() / 2])
             }
         }
     }
 
+    /// Nearest-rank percentile, `p` in `0..=100`.
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        if self.is_empty() {
+            return None;
+        }
+        let rank = (p / 100.0 * self.len() as f64).ceil() as usize;
+        Some(self.0[rank.clamp(1, self.len()) - 1])
+    }
+
+    /// Saturating at `u64::MAX`.
     pub fn sum(&self) -> u64 {
-        self.0.iter().cloned().sum()
+        u64::try_from(self.sum_u128()).unwrap_or(u64::MAX)
+    }
+
+    fn sum_u128(&self) -> u128 {
+        self.0.iter().map(|&d| d as u128).sum()
     }
 
     pub fn mean(&self) -> Option<u64> {
-        if self.len() == 0 {
+        if self.is_empty() {
             None
         } else {
-            Some(self.sum() / self.len() as u64)
+            // Mean fits even if the sum does not.
+            Some((self.sum_u128() / self.len() as u128) as u64)
         }
     }
 
@@ -90,8 +108,8 @@ impl<'a> NumbersSorted<'a> {
     fn filter_3_sigma_inner(&self) -> Option<NumbersSorted<'a>> {
         let std = self.std()?;
         let mean = self.mean()?;
-        let min = mean - std * 3;
-        let max = mean + std * 3;
+        let min = mean.saturating_sub(std.saturating_mul(3));
+        let max = mean.saturating_add(std.saturating_mul(3));
         let nums = self.filter(FilterCond::Ge, min);
         let nums = nums.filter(FilterCond::Le, max);
         Some(nums)
@@ -119,4 +137,26 @@ mod tests {
         );
         assert_eq!(NumbersSorted(&[3, 3, 4, 4]), nums.filter(FilterCond::Gt, 2));
     }
+
+    #[test]
+    fn percentile() {
+        let nums: Vec<u64> = (1..=100).collect();
+        let nums = NumbersSorted(&nums);
+        assert_eq!(Some(99), nums.percentile(99.0));
+        assert_eq!(Some(50), nums.percentile(50.0));
+        assert_eq!(Some(1), nums.percentile(0.0));
+        assert_eq!(Some(100), nums.percentile(100.0));
+        assert_eq!(Some(7), NumbersSorted(&[7]).percentile(99.0));
+        assert_eq!(None, NumbersSorted(&[]).percentile(50.0));
+    }
+
+    #[test]
+    fn med_near_max() {
+        let nums = [u64::MAX - 3, u64::MAX];
+        assert_eq!(Some(u64::MAX - 2), NumbersSorted(&nums).med());
+        // `std * 3` overflows.
+        let nums = [0, u64::MAX];
+        assert_eq!(Some(u64::MAX / 2), NumbersSorted(&nums).med());
+        assert_eq!(NumbersSorted(&nums), NumbersSorted(&nums).filter_3_sigma());
+    }
 }
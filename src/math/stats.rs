@@ -1,9 +1,14 @@
-use std::fmt;
 use std::fmt::Display;
 
+use crate::ansi;
 use crate::experiment_map::ExperimentMap;
 use crate::math::numbers::Numbers;
+use crate::student::TWO_SIDED_95;
+use crate::student::t_table;
+use crate::student::two_sided_p_value;
+use crate::table::column_widths;
 
+#[derive(Clone)]
 pub struct Stats<A> {
     pub count: u64,
     pub mean: A,
@@ -12,6 +17,8 @@ pub struct Stats<A> {
     pub max: A,
     pub std: A,
     pub se: A,
+    /// Total of all samples.
+    pub sum: A,
 }
 
 impl<A> Stats<A> {
@@ -24,6 +31,7 @@ impl<A> Stats<A> {
             max: f(self.max),
             std: f(self.std),
             se: f(self.se),
+            sum: f(self.sum),
         }
     }
 }
@@ -34,67 +42,126 @@ impl Stats<u64> {
         let millis = self.std as f64;
         millis * millis
     }
-}
 
-impl<A: Display + Copy> Stats<A> {
-    pub(crate) fn display_stats_new(stats: &ExperimentMap<Stats<A>>) -> ExperimentMap<String> {
-        struct MultiWriter<'s, A> {
-            vec: ExperimentMap<String>,
-            stats: &'s ExperimentMap<Stats<A>>,
+    /// Half of the 95% confidence interval of the difference of means.
+    pub fn diff_conf_h(&self, other: &Stats<u64>) -> f64 {
+        let degrees_of_freedom = u64::min(self.count - 1, other.count - 1);
+        let t_star = t_table(degrees_of_freedom, TWO_SIDED_95);
+        t_star
+            * f64::sqrt(
+                self.sigma_sq() / (self.count - 1) as f64
+                    + other.sigma_sq() / (other.count - 1) as f64,
+            )
+    }
+
+    /// Half of the 95% confidence interval of the mean.
+    pub fn mean_conf_h(&self) -> f64 {
+        t_table(self.count - 1, TWO_SIDED_95) * self.std as f64 / f64::sqrt(self.count as f64)
+    }
+
+    /// Coefficient of variation in percent.
+    pub fn cv_percent(&self) -> Option<f64> {
+        if self.mean == 0 {
+            return None;
         }
+        Some(self.std as f64 * 100.0 / self.mean as f64)
+    }
 
-        impl<'s, A: Display + Copy> MultiWriter<'s, A> {
-            fn append_n<D: Display>(&mut self, v: impl Fn(&Stats<A>) -> D) -> fmt::Result {
-                use std::fmt::Write;
-                let values: ExperimentMap<String> = self.stats.map(|s| v(s).to_string());
-                let max_len = values.values().map(|s| s.len()).max().unwrap();
-                for (r, s) in self.vec.values_mut().zip(values.values()) {
-                    write!(r, "{:>width$}", s, width = max_len)?;
-                }
-                Ok(())
-            }
+    /// Welch–Satterthwaite degrees of freedom given squared standard errors of the means.
+    fn welch_df(&self, other: &Stats<u64>, var_a: f64, var_b: f64) -> f64 {
+        (var_a + var_b) * (var_a + var_b)
+            / (var_a * var_a / (self.count - 1) as f64 + var_b * var_b / (other.count - 1) as f64)
+    }
 
-            fn append_str(&mut self, s: &str) -> fmt::Result {
-                for r in self.vec.values_mut() {
-                    r.push_str(s);
-                }
-                Ok(())
-            }
+    /// Two-sided p-value of Welch's t-test for equal means.
+    pub fn welch_p_value(&self, other: &Stats<u64>) -> f64 {
+        let var_a = self.sigma_sq() / self.count as f64;
+        let var_b = other.sigma_sq() / other.count as f64;
+        let diff = other.mean as f64 - self.mean as f64;
+        if var_a + var_b == 0.0 {
+            return if diff == 0.0 { 1.0 } else { 0.0 };
+        }
+        let t = diff / f64::sqrt(var_a + var_b);
+        two_sided_p_value(t, self.welch_df(other, var_a, var_b))
+    }
 
-            fn append_column<'d, D: Display + 'd>(
-                &mut self,
-                name: &str,
-                v: impl Fn(&Stats<A>) -> D,
-            ) -> fmt::Result
-            where
-                's: 'd,
+    /// 95% confidence interval of `other.mean / self.mean` by Fieller's theorem.
+    ///
+    /// The interval is unbounded if `self.mean` is not significantly different from zero.
+    pub fn ratio_conf_interval(&self, other: &Stats<u64>) -> (f64, f64) {
+        let mean_a = self.mean as f64;
+        let mean_b = other.mean as f64;
+        let var_a = self.sigma_sq() / self.count as f64;
+        let var_b = other.sigma_sq() / other.count as f64;
+        let ratio = mean_b / mean_a;
+        if var_a + var_b == 0.0 {
+            return (ratio, ratio);
+        }
+        let df = self.welch_df(other, var_a, var_b);
+        let t_star = t_table(df as u64, TWO_SIDED_95);
+        let g = t_star * t_star * var_a / (mean_a * mean_a);
+        if !g.is_finite() || g >= 1.0 {
+            return (f64::NEG_INFINITY, f64::INFINITY);
+        }
+        let h = t_star / mean_a * f64::sqrt(var_b * (1.0 - g) + ratio * ratio * var_a);
+        ((ratio - h) / (1.0 - g), (ratio + h) / (1.0 - g))
+    }
+}
+
+impl<A: Display + Copy> Stats<A> {
+    /// Column names and formatted values.
+    pub fn columns(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("n", self.count.to_string()),
+            ("mean", self.mean.to_string()),
+            ("std", self.std.to_string()),
+            ("se", self.se.to_string()),
+            ("min", self.min.to_string()),
+            ("max", self.max.to_string()),
+            ("med", self.med.to_string()),
+            ("sum", self.sum.to_string()),
+        ]
+    }
+
+    /// Format stats aligned in columns, with the `std` column colored by `std_colors`.
+    pub(crate) fn display_stats_new(
+        stats: &ExperimentMap<Stats<A>>,
+        std_colors: &ExperimentMap<&str>,
+    ) -> ExperimentMap<String> {
+        use std::fmt::Write;
+
+        let columns: ExperimentMap<Vec<(&str, String)>> = stats.map(|s| s.columns());
+        let mut r: ExperimentMap<String> = stats.map(|_| String::new());
+        let values: Vec<Vec<String>> = columns
+            .values()
+            .map(|c| c.iter().map(|(_, v)| v.clone()).collect())
+            .collect();
+        for (i, &width) in column_widths(&values).iter().enumerate() {
+            for ((r, c), color) in r
+                .values_mut()
+                .zip(columns.values())
+                .zip(std_colors.values())
             {
-                if !self.vec.values().next().unwrap().is_empty() {
-                    self.append_str(" ")?;
+                if i != 0 {
+                    r.push(' ');
                 }
-                self.append_str(name)?;
-                self.append_n(v)?;
-                Ok(())
-            }
-
-            fn append_stats(&mut self) -> fmt::Result {
-                self.append_column("n=", |s| s.count)?;
-                self.append_column("mean=", |s| s.mean)?;
-                self.append_column("std=", |s| s.std)?;
-                self.append_column("se=", |s| s.se)?;
-                self.append_column("min=", |s| s.min)?;
-                self.append_column("max=", |s| s.max)?;
-                self.append_column("med=", |s| s.med)?;
-                Ok(())
+                let (color, reset) = match c[i].0 {
+                    "std" if !color.is_empty() => (*color, ansi::RESET),
+                    _ => ("", ""),
+                };
+                write!(
+                    r,
+                    "{}{}={:>width$}{}",
+                    color,
+                    c[i].0,
+                    c[i].1,
+                    reset,
+                    width = width
+                )
+                .unwrap();
             }
         }
-
-        let mut w = MultiWriter {
-            vec: stats.map(|_| String::new()),
-            stats,
-        };
-        w.append_stats().unwrap();
-        w.vec
+        r
     }
 }
 
@@ -111,12 +178,68 @@ pub(crate) fn stats(numbers: &Numbers) -> Option<Stats<u64>> {
         max: numbers.max()?,
         std,
         se,
+        sum: numbers.sum(),
     })
 }
 
+/// Are the first quarter of samples (in run order) significantly greater than the rest.
+///
+/// This usually means warmup was insufficient.
+pub fn early_samples_greater(numbers: &Numbers) -> bool {
+    // Kept samples of streaming mode are not in run order.
+    if numbers.len() < 8 || numbers.is_streaming() {
+        return false;
+    }
+    let (early, rest) = numbers.raw().split_at(numbers.len() / 4);
+    let early = Numbers::from_slice(early);
+    let rest = Numbers::from_slice(rest);
+    let (early, rest) = match (stats(&early), stats(&rest)) {
+        (Some(early), Some(rest)) => (early, rest),
+        _ => return false,
+    };
+    early.mean as f64 - rest.mean as f64 > early.diff_conf_h(&rest)
+}
+
+/// Sarle's bimodality coefficient: 5/9 for a uniform distribution, above for bimodal ones.
+pub fn bimodality_coefficient(samples: &[u64]) -> Option<f64> {
+    let n = samples.len() as f64;
+    if samples.len() < 4 {
+        return None;
+    }
+    let mean = samples.iter().map(|&x| x as f64).sum::<f64>() / n;
+    let moment = |k: i32| {
+        samples
+            .iter()
+            .map(|&x| (x as f64 - mean).powi(k))
+            .sum::<f64>()
+            / n
+    };
+    let (m2, m3, m4) = (moment(2), moment(3), moment(4));
+    if m2 == 0.0 {
+        return None;
+    }
+    // Sample skewness and excess kurtosis, corrected for bias.
+    let skew = m3 / m2.powf(1.5) * f64::sqrt(n * (n - 1.0)) / (n - 2.0);
+    let kurt = ((n + 1.0) * (m4 / (m2 * m2) - 3.0) + 6.0) * (n - 1.0) / ((n - 2.0) * (n - 3.0));
+    Some((skew * skew + 1.0) / (kurt + 3.0 * (n - 1.0) * (n - 1.0) / ((n - 2.0) * (n - 3.0))))
+}
+
+/// Do samples have two modes, so the mean describes neither.
+pub fn appears_bimodal(numbers: &Numbers) -> bool {
+    // Skewness and kurtosis are too noisy with fewer samples.
+    if numbers.len() < 20 {
+        return false;
+    }
+    bimodality_coefficient(numbers.raw()).is_some_and(|bc| bc > 5.0 / 9.0)
+}
+
 #[cfg(test)]
 mod test {
     use crate::math::numbers::Numbers;
+    use crate::math::stats::Stats;
+    use crate::math::stats::appears_bimodal;
+    use crate::math::stats::bimodality_coefficient;
+    use crate::math::stats::early_samples_greater;
     use crate::math::stats::stats;
 
     #[test]
@@ -129,5 +252,68 @@ mod test {
         numbers.push(30u64);
         let stats = stats(&numbers).unwrap();
         assert_eq!(4, stats.se);
+        assert_eq!(120, stats.sum);
+    }
+
+    fn mean_std(count: u64, mean: u64, std: u64) -> Stats<u64> {
+        Stats {
+            count,
+            mean,
+            med: mean,
+            min: mean,
+            max: mean,
+            std,
+            se: 0,
+            sum: mean * count,
+        }
+    }
+
+    #[test]
+    fn mean_conf_h() {
+        // t = 2.262 for 9 degrees of freedom.
+        let h = mean_std(10, 100, 10).mean_conf_h();
+        assert!((h - 7.153).abs() < 0.01, "{}", h);
+    }
+
+    #[test]
+    fn ratio_conf_interval() {
+        let a = mean_std(10, 100, 10);
+        let b = mean_std(10, 50, 5);
+        let (min, max) = a.ratio_conf_interval(&b);
+        assert!((min - 0.454).abs() < 0.002, "{}", min);
+        assert!((max - 0.551).abs() < 0.002, "{}", max);
+
+        let (min, max) = b.ratio_conf_interval(&b);
+        assert!(min < 1.0 && max > 1.0);
+
+        let noisy = mean_std(3, 2, 100);
+        assert_eq!(
+            (f64::NEG_INFINITY, f64::INFINITY),
+            noisy.ratio_conf_interval(&a)
+        );
+    }
+
+    #[test]
+    fn early_samples() {
+        let steady = Numbers::from_slice(&[10, 11, 10, 9, 10, 11, 9, 10, 10, 11, 9, 10]);
+        assert!(!early_samples_greater(&steady));
+        let slow_start = Numbers::from_slice(&[20, 19, 21, 11, 10, 9, 10, 11, 9, 10, 10, 11]);
+        assert!(early_samples_greater(&slow_start));
+        let few = Numbers::from_slice(&[20, 19, 10, 10]);
+        assert!(!early_samples_greater(&few));
+    }
+
+    #[test]
+    fn bimodal() {
+        let two_modes: Vec<u64> = (0..30)
+            .map(|i| 1000 + i)
+            .chain((0..30).map(|i| 2000 + i))
+            .collect();
+        assert!(appears_bimodal(&Numbers::from_slice(&two_modes)));
+        let bell: Vec<u64> = (0..100).map(|i| 100 + i % 5 + i % 7 + i % 11).collect();
+        assert!(!appears_bimodal(&Numbers::from_slice(&bell)));
+        let bc = bimodality_coefficient(&bell).unwrap();
+        assert!((bc - 0.400).abs() < 0.001, "{}", bc);
+        assert!(!appears_bimodal(&Numbers::from_slice(&two_modes[..10])));
     }
 }
@@ -0,0 +1,92 @@
+use std::fmt;
+
+/// Integer displayed with thousands separators, e.g. `1,234,567`.
+#[derive(Copy, Clone, Debug)]
+pub struct Thousands {
+    pub value: u64,
+    /// Print plain digits if false.
+    pub separators: bool,
+}
+
+impl fmt::Display for Thousands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.value.to_string();
+        if !self.separators {
+            return write!(f, "{}", digits);
+        }
+        let mut r = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i != 0 && (digits.len() - i).is_multiple_of(3) {
+                r.push(',');
+            }
+            r.push(c);
+        }
+        write!(f, "{}", r)
+    }
+}
+
+/// Number of thousandths, like `12.345`. Decimals are only shown below 100,
+/// where they are significant; larger numbers are rounded and displayed like
+/// [`Thousands`].
+#[derive(Copy, Clone, Debug)]
+pub struct Milli {
+    pub value: u64,
+    pub separators: bool,
+}
+
+impl fmt::Display for Milli {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.value < 100_000 {
+            return write!(f, "{}.{:03}", self.value / 1000, self.value % 1000);
+        }
+        let thousands = Thousands {
+            // Rounded, `value + 500` may overflow.
+            value: self.value / 1000 + u64::from(self.value % 1000 >= 500),
+            separators: self.separators,
+        };
+        write!(f, "{}", thousands)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::math::thousands::Milli;
+    use crate::math::thousands::Thousands;
+
+    #[test]
+    fn display() {
+        let t = |value| {
+            Thousands {
+                value,
+                separators: true,
+            }
+            .to_string()
+        };
+        assert_eq!("0", t(0));
+        assert_eq!("999", t(999));
+        assert_eq!("1,000", t(1000));
+        assert_eq!("1,234,567", t(1234567));
+        assert_eq!("123,456", t(123456));
+        let plain = Thousands {
+            value: 1234567,
+            separators: false,
+        };
+        assert_eq!("1234567", plain.to_string());
+    }
+
+    #[test]
+    fn milli() {
+        let m = |value| {
+            Milli {
+                value,
+                separators: true,
+            }
+            .to_string()
+        };
+        assert_eq!("0.005", m(5));
+        assert_eq!("99.999", m(99999));
+        assert_eq!("100", m(100_499));
+        assert_eq!("1,235", m(1_234_500));
+        assert_eq!("18,446,744,073,709,552", m(u64::MAX));
+    }
+}
@@ -0,0 +1,82 @@
+use std::convert::TryFrom;
+
+/// Count, sum, extremes and variance of samples in constant memory,
+/// updated with Welford's algorithm.
+#[derive(Clone, Default)]
+pub struct Welford {
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+    mean: f64,
+    /// Sum of squared differences from the mean.
+    m2: f64,
+}
+
+impl Welford {
+    pub fn push(&mut self, d: u64) {
+        if self.count == 0 {
+            self.min = d;
+            self.max = d;
+        }
+        self.count += 1;
+        self.sum += d as u128;
+        self.min = u64::min(self.min, d);
+        self.max = u64::max(self.max, d);
+        let delta = d as f64 - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (d as f64 - self.mean);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Saturating at `u64::MAX`.
+    pub fn sum(&self) -> u64 {
+        u64::try_from(self.sum).unwrap_or(u64::MAX)
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        Some(self.min).filter(|_| self.count != 0)
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        Some(self.max).filter(|_| self.count != 0)
+    }
+
+    /// Rounded down like `NumbersSorted::mean`.
+    pub fn mean(&self) -> Option<u64> {
+        Some(self.sum.checked_div(self.count as u128)? as u64)
+    }
+
+    /// Sample standard deviation.
+    pub fn std(&self) -> Option<u64> {
+        if self.count < 2 {
+            return None;
+        }
+        Some(f64::sqrt(self.m2 / (self.count - 1) as f64) as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::math::numbers::Numbers;
+    use crate::math::welford::Welford;
+
+    #[test]
+    fn same_as_sorted() {
+        let samples = [17, 3, 1000, 250, 251, 9];
+        let mut welford = Welford::default();
+        for &d in &samples {
+            welford.push(d);
+        }
+        let numbers = Numbers::from_slice(&samples);
+        assert_eq!(6, welford.count());
+        assert_eq!(numbers.sum(), welford.sum());
+        assert_eq!(Some(3), welford.min());
+        assert_eq!(Some(1000), welford.max());
+        assert_eq!(numbers.mean(), welford.mean());
+        assert_eq!(numbers.sorted().std(), welford.std());
+    }
+}
@@ -0,0 +1,39 @@
+/// Which way is better for a measure.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum Direction {
+    /// Lower is better.
+    Lower,
+    /// Higher is better.
+    Higher,
+    /// No preference.
+    Neutral,
+}
+
+impl Direction {
+    /// Suffix for the measure name, e.g. `, lower is better`.
+    pub fn name_suffix(&self) -> &'static str {
+        match self {
+            Direction::Lower => ", lower is better",
+            Direction::Higher => ", higher is better",
+            Direction::Neutral => "",
+        }
+    }
+
+    /// Is ratio `b/a` strictly better than `1`.
+    pub fn is_better(&self, ratio: f64) -> bool {
+        match self {
+            Direction::Lower => ratio < 1.0,
+            Direction::Higher => ratio > 1.0,
+            Direction::Neutral => false,
+        }
+    }
+
+    /// Is ratio `b/a` strictly worse than `1`.
+    pub fn is_worse(&self, ratio: f64) -> bool {
+        match self {
+            Direction::Lower => ratio > 1.0,
+            Direction::Higher => ratio < 1.0,
+            Direction::Neutral => false,
+        }
+    }
+}
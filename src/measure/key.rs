@@ -1,16 +1,38 @@
+use std::str::FromStr;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum MeasureKey {
     WallTime,
     MaxRss,
+    UserDefinedMetric,
+    /// Work units per second, derived from wall time.
+    Throughput,
 }
 
 impl MeasureKey {
-    pub const ALL: &'static [MeasureKey] = &[MeasureKey::WallTime, MeasureKey::MaxRss];
+    pub const ALL: &'static [MeasureKey] = &[
+        MeasureKey::WallTime,
+        MeasureKey::MaxRss,
+        MeasureKey::UserDefinedMetric,
+        MeasureKey::Throughput,
+    ];
 
     pub fn index(&self) -> usize {
         match self {
             MeasureKey::WallTime => 0,
             MeasureKey::MaxRss => 1,
+            MeasureKey::UserDefinedMetric => 2,
+            MeasureKey::Throughput => 3,
+        }
+    }
+
+    /// Id used in file names and on the command line, e.g. `wall-time`.
+    pub fn id(&self) -> &'static str {
+        match self {
+            MeasureKey::WallTime => "wall-time",
+            MeasureKey::MaxRss => "max-rss",
+            MeasureKey::UserDefinedMetric => "user-defined-metric",
+            MeasureKey::Throughput => "throughput",
         }
     }
 
@@ -18,7 +40,44 @@ impl MeasureKey {
         match index {
             0 => MeasureKey::WallTime,
             1 => MeasureKey::MaxRss,
+            2 => MeasureKey::UserDefinedMetric,
+            3 => MeasureKey::Throughput,
             _ => panic!("invalid index"),
         }
     }
 }
+
+impl FromStr for MeasureKey {
+    type Err = anyhow::Error;
+
+    /// Id or its short alias, e.g. `rss` for `max-rss`.
+    fn from_str(s: &str) -> anyhow::Result<MeasureKey> {
+        match s {
+            "time" | "wall-time" => Ok(MeasureKey::WallTime),
+            "rss" | "max-rss" => Ok(MeasureKey::MaxRss),
+            "metric" | "user-defined-metric" => Ok(MeasureKey::UserDefinedMetric),
+            "throughput" => Ok(MeasureKey::Throughput),
+            _ => Err(anyhow::anyhow!(
+                "unknown measure {:?}, expecting time, rss, metric or throughput",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::measure::key::MeasureKey;
+
+    #[test]
+    fn parse() {
+        for key in MeasureKey::ALL {
+            assert_eq!(*key, key.id().parse().unwrap());
+        }
+        assert_eq!(MeasureKey::WallTime, "time".parse().unwrap());
+        assert_eq!(MeasureKey::MaxRss, "rss".parse().unwrap());
+        assert_eq!(MeasureKey::UserDefinedMetric, "metric".parse().unwrap());
+        let e = "cpu".parse::<MeasureKey>().unwrap_err().to_string();
+        assert!(e.contains("expecting time, rss"), "{}", e);
+    }
+}
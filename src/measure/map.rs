@@ -5,6 +5,7 @@ use crate::linear_map::LinearMap;
 use crate::measure::key::MeasureKey;
 
 /// Map measure key to a value.
+#[derive(Clone)]
 pub struct MeasureMap<A> {
     values: LinearMap<A>,
 }
@@ -68,6 +69,12 @@ impl<A> MeasureMap<A> {
     pub fn count(&self) -> usize {
         self.values.count()
     }
+
+    pub fn map<'a, B>(&'a self, f: impl FnMut(&'a A) -> B) -> MeasureMap<B> {
+        MeasureMap {
+            values: self.values.map(f),
+        }
+    }
 }
 
 impl<A> Index<MeasureKey> for MeasureMap<A> {
@@ -1,3 +1,4 @@
+pub mod direction;
 pub mod key;
 pub mod map;
 pub mod tr;
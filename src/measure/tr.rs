@@ -1,14 +1,27 @@
 use std::fmt::Display;
 
+use crate::distr_plot::DistrPlots;
 use crate::distr_plot::make_distr_plots;
 use crate::duration::Duration;
+use crate::duration::DurationDisplay;
+use crate::duration::TimeUnit;
 use crate::experiment::Experiment;
 use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
 use crate::math::stats::Stats;
+use crate::math::thousands::Milli;
+use crate::math::thousands::Thousands;
+use crate::measure::direction::Direction;
 use crate::measure::key::MeasureKey;
 use crate::mem_usage::MemUsage;
+use crate::render_stats::RenderOptions;
 use crate::render_stats::render_stats;
+use crate::run_log::RawFormat;
 use crate::run_log::RunLog;
+use crate::summary::MeasureSummary;
+use crate::summary::SummaryReport;
+use crate::svg::Histogram;
+use crate::target::ScoreWeights;
 
 pub(crate) trait Measure {
     type NumberDisplay: Display + Copy;
@@ -19,16 +32,44 @@ pub(crate) trait Measure {
 
     fn name(&self) -> &str;
     fn id(&self) -> &str;
+
+    fn direction(&self) -> Direction;
+
+    /// Suffix of the base unit for exports, e.g. `_seconds`.
+    fn base_unit_suffix(&self) -> &str;
+    /// Multiplier to convert a number to the base unit.
+    fn base_unit_scale(&self) -> f64;
 }
 
-pub struct WallTime;
+pub struct WallTime {
+    name: String,
+    unit: Option<TimeUnit>,
+    sigfigs: Option<usize>,
+}
+
+impl WallTime {
+    /// Display in given unit, or auto-scale each number if not specified.
+    pub fn new(unit: Option<TimeUnit>, sigfigs: Option<usize>) -> WallTime {
+        let name = match unit {
+            Some(unit) => format!("Time (in {})", unit.suffix()),
+            None => "Time".to_owned(),
+        };
+        WallTime {
+            name,
+            unit,
+            sigfigs,
+        }
+    }
+}
 
 impl Measure for WallTime {
     /// Nanoseconds.
-    type NumberDisplay = Duration;
+    type NumberDisplay = DurationDisplay;
 
     fn number_to_display(&self, number: u64) -> Self::NumberDisplay {
         Duration::from_nanos(number)
+            .display_in(self.unit)
+            .with_sigfigs(self.sigfigs)
     }
 
     fn key(&self) -> MeasureKey {
@@ -36,22 +77,46 @@ impl Measure for WallTime {
     }
 
     fn name(&self) -> &str {
-        "Time (in seconds)"
+        &self.name
     }
 
     fn id(&self) -> &str {
         "wall-time"
     }
+
+    fn direction(&self) -> Direction {
+        Direction::Lower
+    }
+
+    fn base_unit_suffix(&self) -> &str {
+        "_seconds"
+    }
+
+    fn base_unit_scale(&self) -> f64 {
+        1e-9
+    }
 }
 
-pub struct MaxRss;
+pub struct MaxRss {
+    thousands: bool,
+}
+
+impl MaxRss {
+    /// Display with thousands separators if `thousands`.
+    pub fn new(thousands: bool) -> MaxRss {
+        MaxRss { thousands }
+    }
+}
 
 impl Measure for MaxRss {
     /// Bytes.
-    type NumberDisplay = u64;
+    type NumberDisplay = Thousands;
 
     fn number_to_display(&self, number: u64) -> Self::NumberDisplay {
-        MemUsage::from_bytes(number).mib()
+        Thousands {
+            value: MemUsage::from_bytes(number).mib(),
+            separators: self.thousands,
+        }
     }
 
     fn key(&self) -> MeasureKey {
@@ -65,56 +130,312 @@ impl Measure for MaxRss {
     fn id(&self) -> &str {
         "max-rss"
     }
+
+    fn direction(&self) -> Direction {
+        Direction::Lower
+    }
+
+    fn base_unit_suffix(&self) -> &str {
+        "_bytes"
+    }
+
+    fn base_unit_scale(&self) -> f64 {
+        1.0
+    }
+}
+
+pub struct UserDefinedMetric {
+    name: String,
+    direction: Direction,
+    thousands: bool,
+}
+
+impl UserDefinedMetric {
+    /// Display with thousands separators if `thousands`.
+    pub fn new(
+        name: Option<&str>,
+        unit: Option<&str>,
+        direction: Direction,
+        thousands: bool,
+    ) -> UserDefinedMetric {
+        let mut full_name = name.unwrap_or("User defined metric").to_owned();
+        if let Some(unit) = unit {
+            full_name.push_str(&format!(" ({})", unit));
+        }
+        full_name.push_str(direction.name_suffix());
+        UserDefinedMetric {
+            name: full_name,
+            direction,
+            thousands,
+        }
+    }
+}
+
+impl Measure for UserDefinedMetric {
+    /// Number printed by the metric script.
+    type NumberDisplay = Thousands;
+
+    fn number_to_display(&self, number: u64) -> Self::NumberDisplay {
+        Thousands {
+            value: number,
+            separators: self.thousands,
+        }
+    }
+
+    fn key(&self) -> MeasureKey {
+        MeasureKey::UserDefinedMetric
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn id(&self) -> &str {
+        "user-defined-metric"
+    }
+
+    fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    fn base_unit_suffix(&self) -> &str {
+        ""
+    }
+
+    fn base_unit_scale(&self) -> f64 {
+        1.0
+    }
+}
+
+pub struct Throughput {
+    thousands: bool,
+}
+
+impl Throughput {
+    /// Display with thousands separators if `thousands`.
+    pub fn new(thousands: bool) -> Throughput {
+        Throughput { thousands }
+    }
+}
+
+impl Measure for Throughput {
+    /// Thousandths of work units per second.
+    type NumberDisplay = Milli;
+
+    fn number_to_display(&self, number: u64) -> Self::NumberDisplay {
+        Milli {
+            value: number,
+            separators: self.thousands,
+        }
+    }
+
+    fn key(&self) -> MeasureKey {
+        MeasureKey::Throughput
+    }
+
+    fn name(&self) -> &str {
+        "Throughput (units/s, higher is better)"
+    }
+
+    fn id(&self) -> &str {
+        "throughput"
+    }
+
+    fn direction(&self) -> Direction {
+        Direction::Higher
+    }
+
+    fn base_unit_suffix(&self) -> &str {
+        "_per_second"
+    }
+
+    fn base_unit_scale(&self) -> f64 {
+        1e-3
+    }
 }
 
 pub trait MeasureDyn {
+    fn key(&self) -> MeasureKey;
     fn name(&self) -> &str;
+    fn id(&self) -> &str;
+    fn direction(&self) -> Direction;
+    fn base_unit_suffix(&self) -> &str;
+    fn base_unit_scale(&self) -> f64;
+    fn format_number(&self, number: u64) -> String;
     fn make_distr_plots(
         &self,
         tests: &ExperimentMap<Experiment>,
         width: usize,
-    ) -> anyhow::Result<ExperimentMap<String>>;
-    fn display_stats(&self, tests: &ExperimentMap<Experiment>) -> ExperimentMap<String>;
+        normalize: bool,
+        log_scale: bool,
+    ) -> DistrPlots;
+    fn display_stats(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        options: &RenderOptions,
+    ) -> ExperimentMap<String>;
+    fn stats_columns(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+    ) -> ExperimentMap<Vec<(&'static str, String)>>;
+    fn histogram_svg(&self, tests: &ExperimentMap<Experiment>, buckets: usize) -> String;
     fn render_stats(
         &self,
         tests: &ExperimentMap<Experiment>,
-        include_distr: bool,
+        summary: &MeasureSummary,
+        options: &RenderOptions,
     ) -> anyhow::Result<String>;
-    fn write_raw(&self, tests: &ExperimentMap<Experiment>, log: &mut RunLog) -> anyhow::Result<()>;
+    fn write_raw(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        log: &mut RunLog,
+        format: RawFormat,
+    ) -> anyhow::Result<()>;
 }
 
 impl<M: Measure> MeasureDyn for M {
+    fn key(&self) -> MeasureKey {
+        self.key()
+    }
+
     fn name(&self) -> &str {
         self.name()
     }
 
+    fn id(&self) -> &str {
+        self.id()
+    }
+
+    fn direction(&self) -> Direction {
+        self.direction()
+    }
+
+    fn base_unit_suffix(&self) -> &str {
+        self.base_unit_suffix()
+    }
+
+    fn base_unit_scale(&self) -> f64 {
+        self.base_unit_scale()
+    }
+
+    fn format_number(&self, number: u64) -> String {
+        self.number_to_display(number).to_string()
+    }
+
     fn make_distr_plots(
         &self,
         tests: &ExperimentMap<Experiment>,
         width: usize,
-    ) -> anyhow::Result<ExperimentMap<String>> {
-        make_distr_plots(tests, width, |t| &t.measures[self.key()])
+        normalize: bool,
+        log_scale: bool,
+    ) -> DistrPlots {
+        make_distr_plots(
+            tests,
+            width,
+            normalize,
+            log_scale,
+            |t| &t.measures[self.key()],
+            |n| self.number_to_display(n).to_string(),
+        )
     }
 
-    fn display_stats(&self, tests: &ExperimentMap<Experiment>) -> ExperimentMap<String> {
-        let stats: ExperimentMap<_> = tests.map(|t| {
+    fn display_stats(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        options: &RenderOptions,
+    ) -> ExperimentMap<String> {
+        let raw_stats: ExperimentMap<_> = tests.map(|t| t.measures[self.key()].stats().unwrap());
+        let std_colors: ExperimentMap<&str> = raw_stats.map(|s| match s.cv_percent() {
+            Some(cv) => options.cv_color(cv),
+            None => "",
+        });
+        let stats: ExperimentMap<_> =
+            raw_stats.map(|s| s.clone().map(|n| self.number_to_display(n)));
+        let mut r = Stats::display_stats_new(&stats, &std_colors);
+        if let Some(k) = options.rolling_mean {
+            let rolling: ExperimentMap<String> = tests.map(|t| {
+                let mean = t.measures[self.key()].tail_mean(k).unwrap();
+                self.number_to_display(mean).to_string()
+            });
+            let width = rolling.values().map(|m| m.chars().count()).max().unwrap();
+            for (r, mean) in r.values_mut().zip(rolling.values()) {
+                r.push_str(&format!(" mean_last{}={:>width$}", k, mean, width = width));
+            }
+        }
+        r
+    }
+
+    fn stats_columns(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+    ) -> ExperimentMap<Vec<(&'static str, String)>> {
+        tests.map(|t| {
             t.measures[self.key()]
                 .stats()
                 .unwrap()
                 .map(|n| self.number_to_display(n))
-        });
-        Stats::display_stats_new(&stats)
+                .columns()
+        })
+    }
+
+    fn histogram_svg(&self, tests: &ExperimentMap<Experiment>, buckets: usize) -> String {
+        let key = self.key();
+        let min = tests
+            .values()
+            .map(|t| t.measures[key].min().unwrap())
+            .min()
+            .unwrap();
+        let max = tests
+            .values()
+            .map(|t| t.measures[key].max().unwrap())
+            .max()
+            .unwrap();
+        let distr: ExperimentMap<_> = tests.map(|t| t.measures[key].distr(buckets, min, max));
+        Histogram {
+            series: tests
+                .zip(&distr)
+                .map(|(name, _, d)| (name.css_color(), &d.counts[..]))
+                .collect(),
+            min_label: self.number_to_display(min).to_string(),
+            max_label: self.number_to_display(max).to_string(),
+        }
+        .render()
     }
 
     fn render_stats(
         &self,
         tests: &ExperimentMap<Experiment>,
-        include_distr: bool,
+        summary: &MeasureSummary,
+        options: &RenderOptions,
     ) -> anyhow::Result<String> {
-        render_stats(tests, include_distr, self, |t| &t.measures[self.key()])
+        render_stats(tests, options, self, summary)
     }
 
-    fn write_raw(&self, tests: &ExperimentMap<Experiment>, log: &mut RunLog) -> anyhow::Result<()> {
+    fn write_raw(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        log: &mut RunLog,
+        format: RawFormat,
+    ) -> anyhow::Result<()> {
+        if let RawFormat::Timestamped { labels } = format {
+            return log.write_raw_timestamped(
+                self.id(),
+                &tests
+                    .values()
+                    .map(|t| {
+                        (
+                            if labels {
+                                t.label.as_str()
+                            } else {
+                                t.name.name()
+                            },
+                            &t.measures[self.key()],
+                            &t.timestamps[self.key()][..],
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            );
+        }
         log.write_raw(
             self.id(),
             &tests
@@ -128,29 +449,154 @@ impl<M: Measure> MeasureDyn for M {
 pub struct AllMeasures(pub Vec<Box<dyn MeasureDyn>>);
 
 impl AllMeasures {
+    /// Measures which have enough samples to compute stats.
+    pub fn with_stats<'a>(
+        &'a self,
+        tests: &'a ExperimentMap<Experiment>,
+    ) -> impl Iterator<Item = &'a dyn MeasureDyn> + 'a {
+        // User defined metric script may fail, so it may have too few samples.
+        self.0.iter().map(|m| &**m).filter(move |m| {
+            let mut recording = tests.values().filter(|t| t.records(m.key())).peekable();
+            recording.peek().is_some() && recording.all(|t| t.measures[m.key()].len() >= 2)
+        })
+    }
+
+    /// Describe measures for which some experiments have substantially fewer samples,
+    /// e.g. because the metric script failed intermittently.
+    pub fn sample_count_warnings(&self, tests: &ExperimentMap<Experiment>) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for measure in &self.0 {
+            let counts: ExperimentMap<usize> = tests.filter_map(|t| {
+                Some(t.measures[measure.key()].len()).filter(|_| t.records(measure.key()))
+            });
+            let max = counts.values().cloned().max().unwrap_or(0);
+            // More than 10% short.
+            let short: Vec<String> = counts
+                .iter()
+                .filter(|(_, &count)| (max - count) * 10 > max)
+                .map(|(name, &count)| {
+                    format!(
+                        "{} has {} samples, {} fewer than {}",
+                        tests[name].label,
+                        count,
+                        max - count,
+                        max
+                    )
+                })
+                .collect();
+            if !short.is_empty() {
+                warnings.push(format!("{}: {}", measure.name(), short.join(", ")));
+            }
+        }
+        warnings
+    }
+
     pub fn render_stats(
         &self,
         tests: &ExperimentMap<Experiment>,
-        include_distr: bool,
+        summary: &SummaryReport,
+        options: &RenderOptions,
     ) -> anyhow::Result<String> {
         let mut s = String::new();
-        for (i, measure) in self.0.iter().enumerate() {
+        for (i, measure_summary) in summary.measures.iter().enumerate() {
             if i != 0 {
-                s.push_str("\n");
+                s.push('\n');
             }
-            s.push_str(&measure.render_stats(tests, include_distr)?);
+            let measure = self.get(measure_summary.key).unwrap();
+            s.push_str(&measure.render_stats(tests, measure_summary, options)?);
         }
         Ok(s)
     }
 
+    /// Weighted sum of each experiment's mean ratio to the reference, inverted for
+    /// measures where higher is better, so the reference scores the sum of weights
+    /// and lower is better. Best first; experiments missing stats of a weighted
+    /// measure are not scored.
+    pub fn composite_scores(
+        &self,
+        summary: &SummaryReport,
+        weights: &ScoreWeights,
+    ) -> Vec<(ExperimentName, f64)> {
+        let mut scores: Vec<(ExperimentName, f64)> = (0..ExperimentName::COUNT)
+            .map(ExperimentName::from_index)
+            .filter_map(|name| {
+                let mut score = 0.0;
+                for &(key, weight) in &weights.0 {
+                    let measure_summary = summary.measure(key)?;
+                    let base = measure_summary.stats[measure_summary.reference].mean as f64;
+                    let mean = measure_summary.stats.get(name)?.mean as f64;
+                    let ratio = match self.get(key)?.direction() {
+                        Direction::Higher => base / mean,
+                        Direction::Lower | Direction::Neutral => mean / base,
+                    };
+                    if !ratio.is_finite() {
+                        return None;
+                    }
+                    score += weight * ratio;
+                }
+                Some((name, score))
+            })
+            .collect();
+        scores.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        scores
+    }
+
+    pub fn get(&self, key: MeasureKey) -> Option<&dyn MeasureDyn> {
+        self.0.iter().map(|m| &**m).find(|m| m.key() == key)
+    }
+
     pub fn write_raw(
         &self,
         tests: &ExperimentMap<Experiment>,
         log: &mut RunLog,
+        format: RawFormat,
     ) -> anyhow::Result<()> {
         for measure in &self.0 {
-            measure.write_raw(tests, log)?;
+            measure.write_raw(tests, log, format)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::measure::key::MeasureKey;
+    use crate::measure::tr::AllMeasures;
+    use crate::measure::tr::MaxRss;
+    use crate::measure::tr::WallTime;
+    use crate::summary::SummaryReport;
+
+    #[test]
+    fn composite_scores() {
+        let mut tests = ExperimentMap::default();
+        // Means of time and max RSS.
+        for (i, &(time, rss)) in [(100, 1000), (80, 1500), (120, 500)].iter().enumerate() {
+            let name = ExperimentName::from_index(i);
+            let mut test = Experiment::new(name, String::new(), String::new());
+            for delta in [-1, 1] {
+                test.push(MeasureKey::WallTime, (time + delta) as u64, 0);
+                test.push(MeasureKey::MaxRss, (rss + delta) as u64, 0);
+            }
+            tests.insert(name, test);
+        }
+        let measures = AllMeasures(vec![
+            Box::new(WallTime::new(None, None)),
+            Box::new(MaxRss::new(true)),
+        ]);
+        let summary = SummaryReport::new(&measures, &tests, ExperimentName::A);
+        let scores =
+            measures.composite_scores(&summary, &"wall-time:0.7,max-rss:0.3".parse().unwrap());
+        let names: Vec<ExperimentName> = scores.iter().map(|&(name, _)| name).collect();
+        assert_eq!(
+            vec![ExperimentName::C, ExperimentName::A, ExperimentName::B],
+            names
+        );
+        // 0.7 * 1.2 + 0.3 * 0.5, 1, 0.7 * 0.8 + 0.3 * 1.5.
+        for (&(_, score), expected) in scores.iter().zip([0.99, 1.0, 1.01]) {
+            assert!((score - expected).abs() < 1e-9, "{:?}", scores);
+        }
+    }
+}
@@ -3,6 +3,11 @@ use std::iter::Sum;
 use std::ops::Add;
 use std::ops::Sub;
 
+use wait4::Wait4;
+
+use crate::sh::SpawnOptions;
+use crate::sh::spawn_sh;
+
 #[derive(Copy, Clone, Default, PartialOrd, Ord, PartialEq, Eq)]
 pub struct MemUsage {
     bytes: u64,
@@ -22,6 +27,15 @@ impl MemUsage {
     }
 }
 
+/// Check whether the platform reports max RSS of child processes.
+///
+/// Some platforms and containers always report zero.
+pub fn max_rss_available() -> anyhow::Result<bool> {
+    let mut process = spawn_sh("true", &SpawnOptions::default())?;
+    let status = process.wait4()?;
+    Ok(status.rusage.maxrss != 0)
+}
+
 impl Add for MemUsage {
     type Output = MemUsage;
 
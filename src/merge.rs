@@ -0,0 +1,273 @@
+//! Combine samples of several run directories, e.g. collected on different hosts.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::env_snapshot::EnvSnapshot;
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::measure::direction::Direction;
+use crate::measure::key::MeasureKey;
+use crate::measure::tr::AllMeasures;
+use crate::measure::tr::MaxRss;
+use crate::measure::tr::MeasureDyn;
+use crate::measure::tr::Throughput;
+use crate::measure::tr::UserDefinedMetric;
+use crate::measure::tr::WallTime;
+
+/// Experiments of a run directory described by `X.run:` and `X.label:` log lines.
+fn parse_log(log: &str) -> anyhow::Result<ExperimentMap<(Experiment, String)>> {
+    let mut tests: ExperimentMap<(Experiment, String)> = ExperimentMap::default();
+    for line in log.lines() {
+        let (key, value) = match line.split_once(": ") {
+            Some(kv) => kv,
+            None => continue,
+        };
+        let (name, field) = match key.split_once('.') {
+            Some(nf) => nf,
+            None => continue,
+        };
+        let name = match ExperimentName::from_name(name) {
+            Some(name) => name,
+            None => continue,
+        };
+        match field {
+            "run" => tests.insert(
+                name,
+                (
+                    Experiment::new(name, String::new(), value.to_owned()),
+                    name.name().to_owned(),
+                ),
+            ),
+            "warmup" | "label" => {
+                let (test, label) = tests
+                    .get_mut(name)
+                    .ok_or_else(|| anyhow::anyhow!("{} before {}.run in log", key, name.name()))?;
+                if field == "warmup" {
+                    test.warmup = value.to_owned();
+                } else {
+                    *label = value.to_owned();
+                }
+            }
+            _ => {}
+        }
+    }
+    if tests.count() == 0 {
+        return Err(anyhow::anyhow!("no experiments in log"));
+    }
+    Ok(tests)
+}
+
+/// Add samples of a raw file written with or without `--raw-timestamps`.
+fn parse_raw(
+    content: &str,
+    key: MeasureKey,
+    tests: &mut ExperimentMap<(Experiment, String)>,
+) -> anyhow::Result<()> {
+    let timestamped = content
+        .lines()
+        .next()
+        .is_some_and(|l| l.split(' ').count() == 3 && l.contains('.'));
+    if !timestamped {
+        let names: Vec<ExperimentName> = tests.keys().collect();
+        for (i, line) in content.lines().enumerate() {
+            let name = *names
+                .get(i)
+                .ok_or_else(|| anyhow::anyhow!("more lines than experiments"))?;
+            for value in line.split_whitespace() {
+                // Start times are not recorded in this format.
+                tests[name].0.push(key, value.parse()?, 0);
+            }
+        }
+        return Ok(());
+    }
+    for line in content.lines() {
+        let mut fields = line.splitn(3, ' ');
+        let (value, timestamp, name) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(value), Some(timestamp), Some(name)) => (value, timestamp, name),
+            _ => return Err(anyhow::anyhow!("malformed line: {:?}", line)),
+        };
+        let name = tests
+            .iter()
+            .find(|(n, (_, label))| label == name || n.name() == name)
+            .map(|(n, _)| n)
+            .ok_or_else(|| anyhow::anyhow!("unknown experiment: {:?}", name))?;
+        let (secs, millis) = timestamp
+            .split_once('.')
+            .ok_or_else(|| anyhow::anyhow!("malformed timestamp: {:?}", timestamp))?;
+        let timestamp = secs.parse::<u64>()? * 1000 + millis.parse::<u64>()?;
+        tests[name].0.push(key, value.parse()?, timestamp);
+    }
+    Ok(())
+}
+
+/// Read experiments and samples of a run directory, e.g. `~/.absh/logs/last`.
+pub fn read_run_dir(dir: &Path) -> anyhow::Result<ExperimentMap<Experiment>> {
+    let context = |e: anyhow::Error| anyhow::anyhow!("{}: {}", dir.display(), e);
+    let log = fs::read_to_string(dir.join("log")).map_err(|e| context(e.into()))?;
+    let mut tests = parse_log(&log).map_err(context)?;
+    for measure in all_measures() {
+        let path = dir.join(format!("raw-{}.txt", measure.id()));
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(context(e.into())),
+        };
+        parse_raw(&content, measure.key(), &mut tests)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    }
+    Ok(tests.map(|(t, _)| t.clone()))
+}
+
+/// Measures which may have raw files, with default display options.
+fn all_measures() -> Vec<Box<dyn MeasureDyn>> {
+    vec![
+        Box::new(WallTime::new(None, None)),
+        Box::new(MaxRss::new(true)),
+        Box::new(UserDefinedMetric::new(None, None, Direction::Neutral, true)),
+        Box::new(Throughput::new(true)),
+    ]
+}
+
+/// Merged experiments and warnings about directories with different experiments.
+pub struct Merged {
+    pub experiments: ExperimentMap<Experiment>,
+    pub measures: AllMeasures,
+    pub warnings: Vec<String>,
+}
+
+/// Environment snapshot of a run directory, `None` if it was written before
+/// snapshots were recorded.
+fn read_env_snapshot(dir: &Path) -> anyhow::Result<Option<EnvSnapshot>> {
+    match fs::read_to_string(dir.join("env.txt")) {
+        Ok(text) => Ok(Some(EnvSnapshot::parse(&text))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("{}: {}", dir.display(), e)),
+    }
+}
+
+/// Concatenate samples of matching experiments and measures of all directories.
+pub fn merge(dirs: &[PathBuf]) -> anyhow::Result<Merged> {
+    let mut experiments: ExperimentMap<Experiment> = ExperimentMap::default();
+    let mut warnings = Vec::new();
+    let mut first_env: Option<(&Path, EnvSnapshot)> = None;
+    for dir in dirs {
+        let tests = read_run_dir(dir)?;
+        match (&first_env, read_env_snapshot(dir)?) {
+            (Some((first_dir, first)), Some(env)) => {
+                let differences = first.differences(&env);
+                if !differences.is_empty() {
+                    warnings.push(format!(
+                        "WARNING: {} was recorded on a different machine than {}, \
+                            results may not be comparable: {}",
+                        dir.display(),
+                        first_dir.display(),
+                        differences.join(", ")
+                    ));
+                }
+            }
+            (None, Some(env)) => first_env = Some((dir, env)),
+            (_, None) => {}
+        }
+        if experiments.count() != 0 {
+            let names = |tests: &ExperimentMap<Experiment>| {
+                tests
+                    .keys()
+                    .map(|n| n.name().to_owned())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            if names(&tests) != names(&experiments) {
+                warnings.push(format!(
+                    "{} has experiments {}, previous directories have {}",
+                    dir.display(),
+                    names(&tests),
+                    names(&experiments)
+                ));
+            }
+        }
+        for test in tests.values() {
+            match experiments.get_mut(test.name) {
+                Some(merged) => {
+                    if merged.run != test.run {
+                        warnings.push(format!(
+                            "{}: {} script differs: {:?}",
+                            dir.display(),
+                            test.name.name(),
+                            test.run
+                        ));
+                    }
+                    for key in MeasureKey::ALL {
+                        for (&value, &timestamp) in
+                            test.measures[*key].raw().iter().zip(&test.timestamps[*key])
+                        {
+                            merged.push(*key, value, timestamp);
+                        }
+                    }
+                }
+                None => experiments.insert(test.name, test.clone()),
+            }
+        }
+    }
+
+    let mut measures = Vec::new();
+    for measure in all_measures() {
+        let key = measure.key();
+        if key != MeasureKey::WallTime && experiments.values().all(|t| t.measures[key].is_empty()) {
+            continue;
+        }
+        // E.g. max RSS recorded with `--mem-b`.
+        for test in experiments.values_mut() {
+            test.skipped[key] = test.measures[key].is_empty();
+        }
+        measures.push(measure);
+    }
+    Ok(Merged {
+        experiments,
+        measures: AllMeasures(measures),
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::experiment_name::ExperimentName;
+    use crate::measure::key::MeasureKey;
+    use crate::merge::parse_log;
+    use crate::merge::parse_raw;
+
+    #[test]
+    fn parse() {
+        let log = "version: 0.0.0\nA.run: true\nB.run: sleep 1\nB.label: slow\nA finished\n";
+        let mut tests = parse_log(log).unwrap();
+        assert_eq!(2, tests.count());
+        assert_eq!("sleep 1", tests[ExperimentName::B].0.run);
+
+        parse_raw("1 2 3\n4 5\n", MeasureKey::WallTime, &mut tests).unwrap();
+        assert_eq!(
+            3,
+            tests[ExperimentName::A].0.measures[MeasureKey::WallTime].len()
+        );
+        assert_eq!(
+            2,
+            tests[ExperimentName::B].0.measures[MeasureKey::WallTime].len()
+        );
+
+        parse_raw(
+            "10 1700000000.123 A\n20 1700000001.000 slow\n",
+            MeasureKey::MaxRss,
+            &mut tests,
+        )
+        .unwrap();
+        let b = &tests[ExperimentName::B].0;
+        assert_eq!(&[20], b.measures[MeasureKey::MaxRss].raw());
+        assert_eq!(vec![1700000001000], b.timestamps[MeasureKey::MaxRss]);
+        assert_eq!(
+            vec![1700000000123],
+            tests[ExperimentName::A].0.timestamps[MeasureKey::MaxRss]
+        );
+    }
+}
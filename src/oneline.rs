@@ -0,0 +1,95 @@
+//! One line summary of a comparison, e.g. for a commit message.
+
+use crate::experiment_name::ExperimentName;
+use crate::measure::key::MeasureKey;
+use crate::summary::SummaryReport;
+
+/// Like `absh: B 12.3% faster than A (p=0.002, n=200)` for the first measure.
+///
+/// `n` is the smaller sample count of the two experiments.
+pub fn oneline(summary: &SummaryReport, other: ExperimentName, base: ExperimentName) -> String {
+    let measure = summary.measures.first();
+    let (other_label, base_label) = (summary.label(other), summary.label(base));
+    let stats = measure.and_then(|m| Some((m, m.stats.get(base)?, m.stats.get(other)?)));
+    let (measure, base_stats, other_stats) = match stats {
+        Some(stats) => stats,
+        None => {
+            return format!(
+                "absh: {} vs {}: not enough samples",
+                other_label, base_label
+            );
+        }
+    };
+    let ratio = other_stats.mean as f64 / base_stats.mean as f64;
+    let (less, more) = match measure.key {
+        MeasureKey::WallTime => ("faster", "slower"),
+        _ => ("lower", "higher"),
+    };
+    let (percent, word) = if ratio < 1.0 {
+        ((1.0 - ratio) * 100.0, less)
+    } else {
+        ((ratio - 1.0) * 100.0, more)
+    };
+    format!(
+        "absh: {} {:.1}% {} than {} (p={:.3}, n={})",
+        other_label,
+        percent,
+        word,
+        base_label,
+        base_stats.welch_p_value(other_stats),
+        u64::min(base_stats.count, other_stats.count),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::math::stats::Stats;
+    use crate::measure::direction::Direction;
+    use crate::measure::key::MeasureKey;
+    use crate::oneline::oneline;
+    use crate::summary::MeasureSummary;
+    use crate::summary::SummaryReport;
+
+    fn stats(count: u64, mean: u64) -> Stats<u64> {
+        Stats {
+            count,
+            mean,
+            med: mean,
+            min: mean,
+            max: mean,
+            std: 10,
+            se: 1,
+            sum: mean * count,
+        }
+    }
+
+    #[test]
+    fn faster() {
+        let mut map = ExperimentMap::default();
+        map.insert(ExperimentName::A, stats(200, 1000));
+        map.insert(ExperimentName::B, stats(210, 877));
+        let mut labels = ExperimentMap::default();
+        labels.insert(ExperimentName::A, "main".to_owned());
+        labels.insert(ExperimentName::B, "B".to_owned());
+        let summary = SummaryReport {
+            measures: vec![MeasureSummary::new(
+                MeasureKey::WallTime,
+                "Time",
+                Direction::Lower,
+                map,
+                ExperimentName::A,
+            )],
+            labels,
+        };
+        assert_eq!(
+            "absh: B 12.3% faster than main (p=0.000, n=200)",
+            oneline(&summary, ExperimentName::B, ExperimentName::A)
+        );
+        assert_eq!(
+            "absh: C vs main: not enough samples",
+            oneline(&summary, ExperimentName::C, ExperimentName::A)
+        );
+    }
+}
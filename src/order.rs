@@ -0,0 +1,134 @@
+//! Order of experiments in each run pair, saved to `order.txt` and replayed
+//! with `--replay-order`.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+use rand::prelude::SliceRandom;
+
+use crate::experiment_name::ExperimentName;
+
+/// Order as a line of letters, like `BAC`.
+pub fn format_order(order: &[ExperimentName]) -> String {
+    order.iter().map(|n| n.name()).collect()
+}
+
+/// Parse a line of letters, which must be a permutation of `names`.
+pub fn parse_order(line: &str, names: &[ExperimentName]) -> anyhow::Result<Vec<ExperimentName>> {
+    let order = line
+        .chars()
+        .map(|c| {
+            ExperimentName::from_name(&c.to_string())
+                .ok_or_else(|| anyhow::anyhow!("unknown experiment {:?} in order {:?}", c, line))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    check_order(&order, names)?;
+    Ok(order)
+}
+
+/// Check that `order` is a permutation of `names`.
+pub fn check_order(order: &[ExperimentName], names: &[ExperimentName]) -> anyhow::Result<()> {
+    let mut sorted = order.to_vec();
+    sorted.sort_by_key(|n| n.index());
+    if sorted != names {
+        return Err(anyhow::anyhow!(
+            "order {:?} does not match experiments {:?}",
+            format_order(order),
+            format_order(names)
+        ));
+    }
+    Ok(())
+}
+
+/// Chooses order of experiments for each pair.
+pub struct PairOrder {
+    random: bool,
+    replay: Option<VecDeque<Vec<ExperimentName>>>,
+    /// Order of every pair instead of `names`, e.g. to always run the slow one last.
+    fixed: Option<Vec<ExperimentName>>,
+}
+
+impl PairOrder {
+    /// Shuffle if `random`, after replaying orders from the file if specified.
+    /// A `fixed` order is used instead of shuffling.
+    pub fn new(
+        random: bool,
+        replay: Option<&Path>,
+        fixed: Option<&[ExperimentName]>,
+        names: &[ExperimentName],
+    ) -> anyhow::Result<PairOrder> {
+        if let Some(fixed) = fixed {
+            check_order(fixed, names)?;
+        }
+        let replay = match replay {
+            Some(path) => Some(
+                fs::read_to_string(path)
+                    .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(|l| parse_order(l, names))
+                    .collect::<anyhow::Result<VecDeque<_>>>()?,
+            ),
+            None => None,
+        };
+        Ok(PairOrder {
+            random: random && fixed.is_none(),
+            replay,
+            fixed: fixed.map(|f| f.to_vec()),
+        })
+    }
+
+    /// Is the order of each pair recorded, because it is not always the same.
+    pub fn recorded(&self) -> bool {
+        self.random || self.replay.is_some()
+    }
+
+    /// Order for the next pair, and true if the replayed orders just ran out.
+    pub fn next(&mut self, names: &[ExperimentName]) -> (Vec<ExperimentName>, bool) {
+        if let Some(replay) = &mut self.replay {
+            if let Some(order) = replay.pop_front() {
+                return (order, false);
+            }
+        }
+        let exhausted = self.replay.take().is_some();
+        let mut order = self.fixed.clone().unwrap_or_else(|| names.to_vec());
+        if self.random {
+            order.shuffle(&mut rand::thread_rng());
+        }
+        (order, exhausted)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::experiment_name::ExperimentName;
+    use crate::order::PairOrder;
+    use crate::order::format_order;
+    use crate::order::parse_order;
+
+    #[test]
+    fn parse() {
+        let names = [ExperimentName::A, ExperimentName::B, ExperimentName::C];
+        let order = parse_order("CAB", &names).unwrap();
+        assert_eq!(
+            vec![ExperimentName::C, ExperimentName::A, ExperimentName::B],
+            order
+        );
+        assert_eq!("CAB", format_order(&order));
+        assert!(parse_order("CA", &names).is_err());
+        assert!(parse_order("CAA", &names).is_err());
+        assert!(parse_order("CAX", &names).is_err());
+    }
+
+    #[test]
+    fn fixed() {
+        let names = [ExperimentName::A, ExperimentName::B, ExperimentName::C];
+        let fixed = [ExperimentName::B, ExperimentName::A, ExperimentName::C];
+        let mut order = PairOrder::new(true, None, Some(&fixed), &names).unwrap();
+        assert!(!order.recorded());
+        assert_eq!((fixed.to_vec(), false), order.next(&names));
+        assert!(PairOrder::new(false, None, Some(&fixed[..2]), &names).is_err());
+    }
+}
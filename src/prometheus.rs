@@ -0,0 +1,71 @@
+//! Prometheus text exposition format, e.g. for node_exporter textfile collector.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::fs_util::write_using_temp;
+use crate::measure::tr::AllMeasures;
+use crate::summary::SummaryReport;
+
+pub fn prometheus_text(measures: &AllMeasures, summary: &SummaryReport) -> anyhow::Result<String> {
+    let mut r = String::new();
+    for measure_summary in &summary.measures {
+        let measure = measures.get(measure_summary.key).unwrap();
+        let metric = format!(
+            "absh_{}{}",
+            measure.id().replace('-', "_"),
+            measure.base_unit_suffix()
+        );
+        writeln!(r, "# HELP {} absh {}", metric, measure.name())?;
+        writeln!(r, "# TYPE {} gauge", metric)?;
+        let scale = measure.base_unit_scale();
+        for (name, stats) in measure_summary.stats.iter() {
+            for (stat, value) in [
+                ("mean", stats.mean),
+                ("median", stats.med),
+                ("stddev", stats.std),
+            ]
+            .iter()
+            {
+                writeln!(
+                    r,
+                    "{}{{experiment=\"{}\",stat=\"{}\"}} {}",
+                    metric,
+                    name.name().to_lowercase(),
+                    stat,
+                    *value as f64 * scale,
+                )?;
+            }
+        }
+
+        // To track how reproducible the benchmark is over time.
+        let metric = format!("absh_{}_cv_ratio", measure.id().replace('-', "_"));
+        writeln!(
+            r,
+            "# HELP {} absh {} coefficient of variation",
+            metric,
+            measure.name()
+        )?;
+        writeln!(r, "# TYPE {} gauge", metric)?;
+        for (name, cv) in measure_summary.cv_percent().iter() {
+            if let Some(cv) = cv {
+                writeln!(
+                    r,
+                    "{}{{experiment=\"{}\"}} {}",
+                    metric,
+                    name.name().to_lowercase(),
+                    cv / 100.0,
+                )?;
+            }
+        }
+    }
+    Ok(r)
+}
+
+pub fn write_prometheus(
+    path: &Path,
+    measures: &AllMeasures,
+    summary: &SummaryReport,
+) -> anyhow::Result<()> {
+    write_using_temp(path, prometheus_text(measures, summary)?)
+}
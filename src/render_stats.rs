@@ -2,75 +2,331 @@ use std::fmt::Write;
 
 use crate::ansi;
 use crate::experiment::Experiment;
+use crate::experiment::recording;
 use crate::experiment_map::ExperimentMap;
-use crate::math::numbers::Numbers;
+use crate::experiment_name::ExperimentName;
+use crate::math::stats::Stats;
 use crate::measure::tr::MeasureDyn;
-use crate::student::t_table;
-use crate::student::TWO_SIDED_95;
+use crate::summary::MeasureSummary;
+use crate::table::render_box;
+
+/// Order of experiments in rendered stats.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum SortKey {
+    /// Experiment name, A first.
+    Name,
+    Mean,
+    Median,
+}
+
+/// How the stats table is drawn.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum TableStyle {
+    /// `name=value` columns, suitable for piping.
+    Plain,
+    /// Header and box-drawing borders.
+    Box,
+}
+
+/// Colors for good and bad results.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum Palette {
+    /// Green for good, red for bad.
+    Default,
+    /// Blue for good, orange for bad, and ▲/▼ before better/worse comparisons.
+    ColorBlind,
+    /// No colors for good and bad, only ▲/▼ markers.
+    Mono,
+}
+
+impl Palette {
+    pub fn good(self) -> &'static str {
+        match self {
+            Palette::Default => ansi::GREEN,
+            Palette::ColorBlind => ansi::BLUE,
+            Palette::Mono => "",
+        }
+    }
+
+    pub fn warn(self) -> &'static str {
+        match self {
+            Palette::Default | Palette::ColorBlind => ansi::YELLOW,
+            Palette::Mono => "",
+        }
+    }
+
+    pub fn bad(self) -> &'static str {
+        match self {
+            Palette::Default => ansi::RED,
+            Palette::ColorBlind => ansi::ORANGE,
+            Palette::Mono => "",
+        }
+    }
+
+    /// Prefix of a comparison which is significantly better or worse,
+    /// so meaning does not depend on color.
+    pub fn marker(self, better: bool) -> &'static str {
+        match (self, better) {
+            (Palette::Default, _) => "",
+            (_, true) => "▲ ",
+            (_, false) => "▼ ",
+        }
+    }
+}
+
+/// How to render stats.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// Include distribution plots.
+    pub include_distr: bool,
+    /// Coefficient of variation percent at which std is highlighted as noisy.
+    pub cv_warn: f64,
+    /// Coefficient of variation percent at which std is highlighted as too noisy.
+    pub cv_bad: f64,
+    pub sort: SortKey,
+    pub sort_desc: bool,
+    /// Significance level below which the best experiment is reported as the winner.
+    pub alpha: f64,
+    /// Also show mean of the last this many samples, to watch convergence.
+    pub rolling_mean: Option<usize>,
+    pub table_style: TableStyle,
+    pub palette: Palette,
+    /// Scale distribution plots to each experiment's sample count rather than
+    /// showing counts.
+    pub normalize_hist: bool,
+    /// Bin distribution plots by the log of the value.
+    pub log_scale: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            include_distr: true,
+            cv_warn: 2.0,
+            cv_bad: 10.0,
+            sort: SortKey::Name,
+            sort_desc: false,
+            alpha: 0.05,
+            rolling_mean: None,
+            table_style: TableStyle::Plain,
+            palette: Palette::Default,
+            normalize_hist: false,
+            log_scale: false,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Color for std given the coefficient of variation in percent.
+    pub fn cv_color(&self, cv: f64) -> &'static str {
+        if cv >= self.cv_bad {
+            self.palette.bad()
+        } else if cv >= self.cv_warn {
+            self.palette.warn()
+        } else {
+            self.palette.good()
+        }
+    }
+}
+
+/// Experiment names in the order they should be displayed.
+fn display_order(
+    stats: &ExperimentMap<Stats<u64>>,
+    options: &RenderOptions,
+) -> Vec<ExperimentName> {
+    let mut order: Vec<ExperimentName> = stats.keys().collect();
+    order.sort_by_key(|&name| match options.sort {
+        SortKey::Name => name.index() as u64,
+        SortKey::Mean => stats[name].mean,
+        SortKey::Median => stats[name].med,
+    });
+    if options.sort_desc {
+        order.reverse();
+    }
+    order
+}
+
+/// Stats of every experiment as a bordered table with a header.
+fn render_box_table(
+    all_tests: &ExperimentMap<Experiment>,
+    options: &RenderOptions,
+    measure: &dyn MeasureDyn,
+    summary: &MeasureSummary,
+    order: &[ExperimentName],
+) -> String {
+    let tests = recording(all_tests, measure.key());
+    let columns = measure.stats_columns(&tests);
+    let mut header = vec![String::new()];
+    header.extend(
+        columns
+            .values()
+            .next()
+            .unwrap()
+            .iter()
+            .map(|(name, _)| (*name).to_owned()),
+    );
+    if let Some(k) = options.rolling_mean {
+        header.push(format!("mean_last{}", k));
+    }
+
+    let label = |name: ExperimentName| all_tests[name].name_colored();
+    let mut rows = Vec::new();
+    for &name in order {
+        let mut row = vec![label(name)];
+        let color = match summary.stats[name].cv_percent() {
+            Some(cv) => options.cv_color(cv),
+            None => "",
+        };
+        for (column, value) in &columns[name] {
+            row.push(match *column {
+                "std" if !color.is_empty() => format!("{}{}{}", color, value, ansi::RESET),
+                _ => value.clone(),
+            });
+        }
+        if let Some(k) = options.rolling_mean {
+            let mean = tests[name].measures[measure.key()].tail_mean(k).unwrap();
+            row.push(measure.format_number(mean));
+        }
+        rows.push(row);
+    }
+    for test in all_tests.values().filter(|t| !t.records(measure.key())) {
+        rows.push(vec![label(test.name), "n/a".to_owned()]);
+    }
+    render_box(&header, &rows)
+}
 
 pub(crate) fn render_stats(
     tests: &ExperimentMap<Experiment>,
-    include_distr: bool,
+    options: &RenderOptions,
     measure: &dyn MeasureDyn,
-    numbers: impl Fn(&Experiment) -> &Numbers,
+    summary: &MeasureSummary,
 ) -> anyhow::Result<String> {
     let mut r = String::new();
 
-    let stats: ExperimentMap<_> = tests.map(|t| numbers(t).stats().unwrap());
+    let all_tests = tests;
+    let tests = &recording(all_tests, measure.key());
+
+    let stats_str: ExperimentMap<String> = measure.display_stats(tests, options);
 
-    let stats_str: ExperimentMap<String> = measure.display_stats(tests);
+    let stats_width = stats_str
+        .values()
+        .map(|s| ansi::strip_csi(s).chars().count())
+        .max()
+        .unwrap();
 
-    let stats_width = stats_str.values().map(|s| s.len()).max().unwrap();
+    let distr_plots = measure.make_distr_plots(
+        tests,
+        stats_width.saturating_sub(8),
+        options.normalize_hist,
+        options.log_scale,
+    );
 
-    let distr_plots = measure.make_distr_plots(&tests, stats_width - 8)?;
+    let order = display_order(&summary.stats, options);
+    // Labels may differ in length.
+    let label_width = all_tests
+        .values()
+        .map(|t| t.label.chars().count())
+        .max()
+        .unwrap();
 
     writeln!(r, "{}:", measure.name())?;
-    for (_name, test, stats) in tests.zip(&stats_str) {
-        writeln!(
-            r,
-            "{color}{name}{reset}: {stats}",
-            name = test.name,
-            color = test.name.color(),
-            reset = ansi::RESET,
-        )?;
+    match options.table_style {
+        TableStyle::Box => {
+            r.push_str(&render_box_table(
+                all_tests, options, measure, summary, &order,
+            ));
+        }
+        TableStyle::Plain => {
+            for &name in &order {
+                let test = &tests[name];
+                writeln!(
+                    r,
+                    "{color}{name:<width$}{reset}: {stats}",
+                    name = test.label,
+                    width = label_width,
+                    stats = stats_str[name],
+                    color = test.name.color(),
+                    reset = ansi::RESET,
+                )?;
+            }
+            for test in all_tests.values().filter(|t| !t.records(measure.key())) {
+                writeln!(
+                    r,
+                    "{color}{name:<width$}{reset}: n/a",
+                    name = test.label,
+                    width = label_width,
+                    color = test.name.color(),
+                    reset = ansi::RESET,
+                )?;
+            }
+        }
     }
-    for (_name, test, plot) in tests.zip(&distr_plots) {
-        if include_distr {
+    for &name in &order {
+        let test = &tests[name];
+        if options.include_distr {
             writeln!(
                 r,
-                "{color}{name}{reset}: distr=[{plot}]",
-                name = test.name,
+                "{color}{name:<width$}{reset}: distr=[{plot}]",
+                name = test.label,
+                width = label_width,
+                plot = distr_plots.plots[name],
                 color = test.name.color(),
                 reset = ansi::RESET,
             )?;
         }
     }
+    let scale = match (distr_plots.full_block, distr_plots.full_block_percent) {
+        (Some(full_block), _) => Some(format!(
+            "█={} sample{}",
+            full_block,
+            if full_block == 1 { "" } else { "s" }
+        )),
+        (None, Some(percent)) => Some(format!("█={:.1}% of samples", percent)),
+        (None, None) => None,
+    };
+    if let (true, Some(scale)) = (options.include_distr, scale) {
+        // Aligned with `: distr=[`.
+        match &distr_plots.axis {
+            Some(axis) => writeln!(
+                r,
+                "{:<width$}   axis=[{}]{} {}",
+                "",
+                axis,
+                if options.log_scale { " (log)" } else { "" },
+                scale,
+                width = label_width
+            )?,
+            None => writeln!(r, "{:<width$}   {}", "", scale, width = label_width)?,
+        }
+    }
 
-    let mut stats_iter = stats.iter();
-    let (a_name, stats_a) = stats_iter.next().unwrap();
-    for (b_name, stats_b) in stats_iter {
-        let degrees_of_freedom = u64::min(stats_a.count as u64 - 1, stats_b.count as u64 - 1);
-        let t_star = t_table(degrees_of_freedom, TWO_SIDED_95);
-
-        // Half of a confidence interval
-        let conf_h = t_star
-            * f64::sqrt(
-                stats_a.sigma_sq() / (stats_a.count - 1) as f64
-                    + stats_b.sigma_sq() / (stats_b.count - 1) as f64,
-            );
-
-        // Quarter of a confidence interval
-        let conf_q = conf_h / 2.0;
+    for comparison in &summary.comparisons {
+        // Color only when the whole confidence interval is on one side of 1.
+        let (color, marker) = if comparison.is_better(summary.direction) {
+            (options.palette.good(), options.palette.marker(true))
+        } else if comparison.is_worse(summary.direction) {
+            (options.palette.bad(), options.palette.marker(false))
+        } else {
+            ("", "")
+        };
+        let reset = if color.is_empty() { "" } else { ansi::RESET };
 
-        let b_a_min = (stats_b.mean as f64 - conf_q) / (stats_a.mean as f64 + conf_q);
-        let b_a_max = (stats_b.mean as f64 + conf_q) / (stats_a.mean as f64 - conf_q);
+        writeln!(
+            r,
+            "{color}{marker}{b_name}/{a_name}: {b_a:.3}x [{b_a_min:.3}, {b_a_max:.3}] (95% conf){reset}",
+            b_name = all_tests[comparison.other].label,
+            a_name = all_tests[comparison.base].label,
+            b_a = comparison.ratio,
+            b_a_min = comparison.ratio_min,
+            b_a_max = comparison.ratio_max,
+        )?;
+    }
 
+    if let Some(verdict) = summary.verdict(options.alpha) {
         writeln!(
             r,
-            "{b_name}/{a_name}: {b_a:.3} {b_a_min:.3}..{b_a_max:.3} (95% conf)",
-            b_a = stats_b.mean as f64 / stats_a.mean as f64,
-            b_a_min = b_a_min,
-            b_a_max = b_a_max,
+            "{}",
+            verdict.describe(&all_tests.map(|t| t.label.clone()))
         )?;
     }
 
@@ -0,0 +1,121 @@
+//! Periodic sampling of resident set size while a script runs (Linux only).
+
+use std::fs;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::thread::JoinHandle;
+
+use crate::duration::Duration;
+use crate::mem_usage::MemUsage;
+
+/// Is RSS of running processes available on this platform.
+pub fn rss_sampling_available() -> bool {
+    cfg!(target_os = "linux") && fs::metadata("/proc/self/stat").is_ok()
+}
+
+/// Process group and RSS in pages from `/proc/<pid>/stat` content.
+fn parse_stat(stat: &str) -> Option<(u32, u64)> {
+    // Command name may contain spaces and parentheses.
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    // Fields after the command name start with the third, `state`.
+    let pgrp = fields.get(5 - 3)?.parse().ok()?;
+    let rss_pages = fields.get(24 - 3)?.parse().ok()?;
+    Some((pgrp, rss_pages))
+}
+
+#[cfg(unix)]
+fn page_size() -> u64 {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as u64 }
+}
+
+#[cfg(not(unix))]
+fn page_size() -> u64 {
+    4096
+}
+
+/// Total RSS of processes in the process group.
+fn group_rss(pgid: u32) -> Option<MemUsage> {
+    let mut pages = 0;
+    let mut found = false;
+    for entry in fs::read_dir("/proc").ok()? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+        {
+            continue;
+        }
+        // Process may exit while we are reading.
+        let stat = match fs::read_to_string(entry.path().join("stat")) {
+            Ok(stat) => stat,
+            Err(_) => continue,
+        };
+        if let Some((pgrp, rss_pages)) = parse_stat(&stat) {
+            if pgrp == pgid {
+                pages += rss_pages;
+                found = true;
+            }
+        }
+    }
+    if found {
+        Some(MemUsage::from_bytes(pages * page_size()))
+    } else {
+        None
+    }
+}
+
+/// Thread sampling RSS of a process group.
+pub struct RssSampler {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<Vec<MemUsage>>,
+}
+
+impl RssSampler {
+    /// Start sampling the process group every `interval`.
+    pub fn start(pgid: u32, interval: Duration) -> RssSampler {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut samples = Vec::new();
+            loop {
+                if let Some(rss) = group_rss(pgid) {
+                    samples.push(rss);
+                }
+                match stopped.recv_timeout(std::time::Duration::from_nanos(interval.nanos())) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => return samples,
+                }
+            }
+        });
+        RssSampler { stop, thread }
+    }
+
+    /// Stop sampling and return the average RSS, `None` if no samples were taken.
+    pub fn finish(self) -> Option<MemUsage> {
+        let _ = self.stop.send(());
+        let samples = self.thread.join().unwrap();
+        if samples.is_empty() {
+            return None;
+        }
+        let sum: MemUsage = samples.iter().copied().sum();
+        Some(MemUsage::from_bytes(sum.bytes() / samples.len() as u64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rss_sampler::parse_stat;
+
+    #[test]
+    fn stat() {
+        let stat = "1234 (my (prog) x) S 1 1230 1230 0 -1 4194560 100 0 0 0 1 2 0 0 20 0 1 0 \
+            500 10000000 321 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 3 0 0 0 0 0";
+        assert_eq!(Some((1230, 321)), parse_stat(stat));
+        assert_eq!(None, parse_stat("1234 (sh) S 1"));
+    }
+}
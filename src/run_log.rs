@@ -3,26 +3,90 @@ use std::fmt;
 use std::fmt::Write as _;
 use std::fs;
 use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::LineWriter;
+use std::io::Write as _;
 use std::path::Path;
 use std::path::PathBuf;
 use std::time::SystemTime;
 
 use crate::ansi::strip_csi;
+use crate::console_writer::ColorChoice;
 use crate::console_writer::ConsoleWriter;
+use crate::env_snapshot::EnvSnapshot;
+use crate::event::Event;
 use crate::fs_util::write_using_temp;
 use crate::math::numbers::Numbers;
 use crate::maybe_strip_csi_writer::MaybeStripCsiWriter;
 use crate::shell::shell_quote_args;
 
+/// What is written to the run log directory besides the plain text `log`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Only the plain text log.
+    Text,
+    /// Also `events.jsonl`, a JSON line for each run start, result and stats render.
+    Json,
+}
+
+/// How much is printed to stderr, set with `-q` and `-v`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Final stats, warnings and errors only.
+    Quiet,
+    /// Also progress of each run, and stats as they are updated.
+    Normal,
+    /// Also environment, working directory and shell of each run.
+    Verbose,
+    /// Also raw values of each sample as it is recorded.
+    Debug,
+}
+
+impl Verbosity {
+    /// `-q`, or the number of `-v` flags.
+    pub fn from_flags(quiet: bool, verbose: u8) -> Verbosity {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Verbosity::Quiet => "quiet",
+            Verbosity::Normal => "normal",
+            Verbosity::Verbose => "verbose",
+            Verbosity::Debug => "debug",
+        }
+    }
+}
+
+/// Format of raw sample files.
+#[derive(Copy, Clone, Debug)]
+pub enum RawFormat {
+    /// Line of space separated values per experiment.
+    Values,
+    /// Line per sample: value, timestamp and experiment letter or label.
+    Timestamped { labels: bool },
+}
+
 pub struct RunLog {
     name: PathBuf,
     last: Option<PathBuf>,
-    file: File,
+    /// Line buffered, so the log can be followed with `tail -f`.
+    file: LineWriter<File>,
+    /// `events.jsonl`, see [`LogFormat::Json`].
+    events: Option<LineWriter<File>>,
     console_writer: ConsoleWriter,
+    verbosity: Verbosity,
 }
 
 pub struct BothLogAndStderr<'a> {
     log: &'a mut RunLog,
+    stderr: bool,
+    file: bool,
 }
 
 impl RunLog {
@@ -34,7 +98,7 @@ impl RunLog {
         self.last.as_deref()
     }
 
-    pub fn open() -> RunLog {
+    pub fn open(color: ColorChoice) -> RunLog {
         let home_dir = dirs::home_dir().expect("home_dir not found");
         let mut absh_logs_dir = home_dir.clone();
         absh_logs_dir.push(".absh/logs");
@@ -48,12 +112,14 @@ impl RunLog {
         );
         name.push(&id);
 
-        fs::create_dir_all(&name).expect(&format!("failed to create dir {}", name.display()));
+        fs::create_dir_all(&name)
+            .unwrap_or_else(|_| panic!("failed to create dir {}", name.display()));
 
         let mut log = name.clone();
         log.push("log");
 
-        let file = File::create(&log).expect(&format!("failed to create {}", log.display()));
+        let file =
+            File::create(&log).unwrap_or_else(|_| panic!("failed to create {}", log.display()));
 
         #[cfg(unix)]
         let last = {
@@ -68,15 +134,61 @@ impl RunLog {
         let last = { None };
 
         RunLog {
-            console_writer: ConsoleWriter::auto(),
+            console_writer: ConsoleWriter::new(color),
             name,
-            file,
+            file: LineWriter::new(file),
+            events: None,
             last,
+            verbosity: Verbosity::Normal,
         }
     }
 
-    pub fn both_log_and_stderr(&mut self) -> BothLogAndStderr {
-        BothLogAndStderr { log: self }
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) {
+        self.verbosity = verbosity;
+    }
+
+    /// Whether output of `level` is printed to stderr.
+    pub fn shows(&self, level: Verbosity) -> bool {
+        self.verbosity >= level
+    }
+
+    /// Start writing events to `events.jsonl`.
+    pub fn enable_events(&mut self) -> anyhow::Result<()> {
+        let path = self.name.join("events.jsonl");
+        let file = File::create(&path)
+            .map_err(|e| anyhow::anyhow!("failed to create {}: {}", path.display(), e))?;
+        self.events = Some(LineWriter::new(file));
+        Ok(())
+    }
+
+    /// Append the event if enabled with [`RunLog::enable_events`].
+    pub fn event(&mut self, event: &Event) -> anyhow::Result<()> {
+        if let Some(events) = &mut self.events {
+            let time_ms = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64);
+            writeln!(events, "{}", event.to_json(time_ms))?;
+        }
+        Ok(())
+    }
+
+    /// Printed regardless of verbosity, e.g. warnings and final stats.
+    pub fn both_log_and_stderr(&mut self) -> BothLogAndStderr<'_> {
+        BothLogAndStderr {
+            log: self,
+            stderr: true,
+            file: true,
+        }
+    }
+
+    /// Printed if verbosity is at least `level`. The log gets everything up to
+    /// normal even when quiet, so it still shows the progress of each run.
+    pub fn at(&mut self, level: Verbosity) -> BothLogAndStderr<'_> {
+        BothLogAndStderr {
+            stderr: self.shows(level),
+            file: self.verbosity.max(Verbosity::Normal) >= level,
+            log: self,
+        }
     }
 
     pub fn log_only(&mut self) -> impl fmt::Write + '_ {
@@ -99,7 +211,7 @@ impl RunLog {
                 }
                 write!(r, "{}", d)?;
             }
-            write!(r, "\n")?;
+            writeln!(r)?;
             Ok(())
         }
 
@@ -111,35 +223,81 @@ impl RunLog {
         Ok(())
     }
 
-    pub fn write_graph(&mut self, graph: &str) -> anyhow::Result<()> {
+    /// Write one sample per line: value, timestamp in seconds and experiment letter or label.
+    pub fn write_raw_timestamped(
+        &mut self,
+        id: &str,
+        samples: &[(&str, &Numbers, &[u64])],
+    ) -> anyhow::Result<()> {
+        let mut content = String::new();
+        for (name, numbers, timestamps) in samples {
+            for (value, timestamp) in numbers.iter().zip(timestamps.iter()) {
+                writeln!(
+                    content,
+                    "{} {}.{:03} {}",
+                    value,
+                    timestamp / 1000,
+                    timestamp % 1000,
+                    name,
+                )?;
+            }
+        }
+        write_using_temp(self.name.join(format!("raw-{}.txt", id)), content)?;
+        Ok(())
+    }
+
+    /// Append order of experiments in a pair to `order.txt`.
+    pub fn append_order(&mut self, order: &str) -> anyhow::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.name.join("order.txt"))?;
+        writeln!(file, "{}", order)?;
+        Ok(())
+    }
+
+    /// Write `graph` as is and without colors, and `report` to `report.md`.
+    pub fn write_graph(&mut self, graph: &str, report: &str) -> anyhow::Result<()> {
         write_using_temp(self.name.join("graph.txt"), graph)?;
         write_using_temp(self.name.join("graph-bw.txt"), strip_csi(graph))?;
 
         let report_md = format!(
             "```\n{}\n```\n```\n{}```\n",
             Self::args_str(),
-            strip_csi(graph),
+            strip_csi(report),
         );
         write_using_temp(self.name.join("report.md"), report_md)?;
         Ok(())
     }
 
-    fn args_str() -> String {
+    pub fn write_graph_svg(&mut self, svg: &str) -> anyhow::Result<()> {
+        write_using_temp(self.name.join("graph.svg"), svg)
+    }
+
+    pub fn args_str() -> String {
         shell_quote_args(env::args())
     }
 
     pub fn write_args(&mut self) -> anyhow::Result<()> {
         let mut args = Self::args_str();
-        args.push_str("\n");
+        args.push('\n');
         write_using_temp(self.name.join("args.txt"), args)?;
         Ok(())
     }
+
+    pub fn write_env_snapshot(&mut self, snapshot: &EnvSnapshot) -> anyhow::Result<()> {
+        write_using_temp(self.name.join("env.txt"), snapshot.to_text())
+    }
 }
 
 impl fmt::Write for BothLogAndStderr<'_> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.log.console_writer.write_str(s)?;
-        write!(self.log.log_only(), "{}", s)?;
+        if self.stderr {
+            self.log.console_writer.write_str(s)?;
+        }
+        if self.file {
+            write!(self.log.log_only(), "{}", s)?;
+        }
         Ok(())
     }
 }
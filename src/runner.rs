@@ -0,0 +1,2426 @@
+//! Benchmark loop, used by the command line tool and usable as a library.
+
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use wait4::Wait4;
+
+use crate::ansi;
+use crate::capture::Capture;
+use crate::console_writer::ColorChoice;
+use crate::console_writer::ConsoleWriter;
+use crate::console_writer::write_or_exit;
+use crate::duration::Duration;
+use crate::duration::TimeUnit;
+use crate::env_snapshot::EnvSnapshot;
+use crate::event::Event;
+use crate::experiment::Experiment;
+use crate::experiment::recording;
+use crate::experiment::set_or_push;
+use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::fs_util::TempDir;
+use crate::html_report::graph_svg;
+use crate::html_report::write_html_report;
+use crate::idle::IdleThresholds;
+use crate::idle::wait_for_idle;
+use crate::math::correlation::pearson;
+use crate::math::numbers::Numbers;
+use crate::math::stats::Stats;
+use crate::math::stats::appears_bimodal;
+use crate::math::stats::early_samples_greater;
+use crate::measure::direction::Direction;
+use crate::measure::key::MeasureKey;
+use crate::measure::map::MeasureMap;
+use crate::measure::tr::AllMeasures;
+use crate::measure::tr::MaxRss;
+use crate::measure::tr::MeasureDyn;
+use crate::measure::tr::Throughput;
+use crate::measure::tr::UserDefinedMetric;
+use crate::measure::tr::WallTime;
+use crate::mem_usage::MemUsage;
+use crate::mem_usage::max_rss_available;
+use crate::oneline::oneline;
+use crate::order::PairOrder;
+use crate::order::format_order;
+use crate::prometheus::write_prometheus;
+use crate::render_stats::Palette;
+use crate::render_stats::RenderOptions;
+use crate::render_stats::SortKey;
+use crate::render_stats::TableStyle;
+use crate::rss_sampler::RssSampler;
+use crate::rss_sampler::rss_sampling_available;
+use crate::run_log::LogFormat;
+use crate::run_log::RawFormat;
+use crate::run_log::RunLog;
+use crate::run_log::Verbosity;
+use crate::server::Server;
+use crate::sh::SpawnOptions;
+use crate::sh::command_line;
+use crate::sh::preflight;
+use crate::sh::repeat_script;
+use crate::sh::sh_stdout;
+use crate::sh::sh_stdout_with_env;
+use crate::sh::spawn_sh;
+use crate::shutdown;
+use crate::summary::SummaryReport;
+use crate::tap::tap_report;
+use crate::target::ScoreWeights;
+use crate::target::Target;
+use crate::target::WorseLimit;
+use crate::timeout::Signal;
+use crate::timeout::Timeout;
+use crate::timeout::Watchdog;
+
+/// Benchmark configuration, see command line help for details.
+pub struct RunConfig {
+    /// A variant script.
+    pub a: String,
+    pub b: Option<String>,
+    pub c: Option<String>,
+    pub d: Option<String>,
+    pub e: Option<String>,
+    /// A variant warmup script.
+    pub aw: Option<String>,
+    pub bw: Option<String>,
+    pub cw: Option<String>,
+    pub dw: Option<String>,
+    pub ew: Option<String>,
+    /// A variant label shown instead of `A`.
+    pub name_a: Option<String>,
+    pub name_b: Option<String>,
+    pub name_c: Option<String>,
+    pub name_d: Option<String>,
+    pub name_e: Option<String>,
+    /// Run each warmup script at least this many times before each run.
+    pub warmup_runs: u32,
+    /// Repeat each warmup script before each run until it took at least this long.
+    pub warmup_time: Duration,
+    /// Script run once before all iterations.
+    pub global_warmup: Option<String>,
+    /// Run global and per-experiment warmup scripts and stop without measuring.
+    pub warmup_only: bool,
+    /// Script run after each run, not measured, e.g. to drop caches.
+    pub between: Option<String>,
+    /// Random order of experiments in each pair, saved to `order.txt` in the log dir.
+    pub random_order: bool,
+    /// File with order of experiments for each pair, like `order.txt`.
+    pub replay_order: Option<PathBuf>,
+    /// Run experiments of every pair in this order, overriding `random_order`.
+    pub order: Option<Vec<ExperimentName>>,
+    /// Number of iterations to ignore.
+    pub ignore_first: u32,
+    /// Stop after this many successful iterations.
+    ///
+    /// Only successful runs are counted, and the session stops once every experiment
+    /// has this many. Failed runs do not count, so with `keep_going` an experiment which
+    /// keeps failing can prevent reaching it; a shortfall is reported when stopped
+    /// by `max_duration` or a signal.
+    pub iterations: Option<u32>,
+    /// Neither `max_duration` nor `auto` stop the session before every experiment
+    /// has this many successful runs; only a signal does.
+    pub min_iterations: u32,
+    /// When `iterations` are reached and stdin is a terminal, ask whether to
+    /// run more, keeping the samples so far.
+    pub prompt: bool,
+    /// Stop running an experiment once the 95% confidence interval of its mean wall time
+    /// is within this percent of the mean.
+    pub auto: Option<f64>,
+    /// Run the script this many times in each measured run, by index of `ExperimentName`,
+    /// and record the mean, so fast and slow experiments get comparable per-run times.
+    pub inner_runs: [u32; ExperimentName::COUNT],
+    pub mem: bool,
+    /// Measure max RSS only for these experiments, unless `mem` is set.
+    pub mem_experiments: Vec<ExperimentName>,
+    /// Sample RSS while the script runs and log the average with the peak (Linux only).
+    pub avg_rss: bool,
+    /// Script run after each successful run, which prints a number to record.
+    pub metric: Option<String>,
+    pub metric_name: Option<String>,
+    pub metric_unit: Option<String>,
+    pub metric_direction: Direction,
+    pub metric_offset: u64,
+    pub metric_scope: MetricScope,
+    /// File the run script writes the metric to, read after each run instead of
+    /// running `metric`.
+    pub metric_file: Option<PathBuf>,
+    /// Remove `metric_file` after reading it.
+    pub metric_file_delete: bool,
+    /// Warn when a metric sample differs from the running median by more than this factor.
+    pub metric_sanity: Option<f64>,
+    /// Each run does this much work, recorded as throughput in units per second.
+    pub work_units: Option<u64>,
+    pub time_offset: Duration,
+    pub isolate: bool,
+    /// Check that scripts parse and their first command exists before running anything.
+    pub preflight: bool,
+    pub env: Vec<(String, String)>,
+    pub stdin_file: Option<PathBuf>,
+    /// Capture output of warmup and run scripts instead of inheriting stdout and stderr.
+    pub capture: bool,
+    /// Number of last captured output lines printed when a script fails.
+    pub fail_output_lines: usize,
+    pub strict: bool,
+    pub max_duration: Option<Duration>,
+    pub retries: u32,
+    pub keep_going: bool,
+    /// Run is finished when this file appears after the script returns, e.g. for
+    /// background work. Waiting is measured and limited by `timeout`.
+    pub wait_for: Option<PathBuf>,
+    pub timeout: Option<Duration>,
+    pub timeout_signal: Signal,
+    pub timeout_grace: Duration,
+    /// Load script: run scripts start servers, and this script is run against each
+    /// for `window`, after which `metric` is recorded and the server is stopped.
+    pub load: Option<String>,
+    pub window: Duration,
+    pub time_unit: Option<TimeUnit>,
+    /// Significant figures of wall times, other measures are integers.
+    pub sigfigs: Option<usize>,
+    /// Display RSS and metric stats without thousands separators.
+    pub no_thousands: bool,
+    pub log_full_graph: bool,
+    /// Write start timestamp next to each sample in raw files.
+    pub raw_timestamps: bool,
+    /// Use labels rather than letters in raw files with timestamps.
+    pub raw_labels: bool,
+    /// Skip stats and write raw files only when finished.
+    pub raw_only: bool,
+    pub no_plots: bool,
+    /// Write histograms to `graph.svg` in the run log directory.
+    pub graph_svg: bool,
+    /// Print stats every N iterations, must be positive.
+    pub report_every: u32,
+    pub html: Option<PathBuf>,
+    pub prometheus: Option<PathBuf>,
+    /// Measures included in `html`, `prometheus` and `report.md`, all if empty.
+    pub export_measures: Vec<MeasureKey>,
+    /// Print TAP report to stdout when finished.
+    pub tap: bool,
+    /// Print a one line summary of the first experiment compared with the second
+    /// to stdout when finished.
+    pub oneline: Option<(ExperimentName, ExperimentName)>,
+    /// Experiment other experiments are compared with.
+    pub reference: ExperimentName,
+    /// Absolute thresholds checked at the end.
+    pub targets: Vec<Target>,
+    /// Fail if an experiment is significantly faster than the reference by more than
+    /// this percent.
+    pub fail_if_faster: Option<f64>,
+    /// Fail if an experiment is significantly worse than the reference by more than
+    /// the limit of the measure.
+    pub fail_if_worse: Vec<WorseLimit>,
+    /// Print a composite score of each experiment, ranked.
+    pub score: Option<ScoreWeights>,
+    pub cool_to_idle: bool,
+    pub idle_load: f64,
+    pub idle_temp: f64,
+    pub idle_max_wait: Duration,
+    pub drop_outliers: bool,
+    /// Bounded memory for long sessions: stats other than the median are computed
+    /// incrementally, and once there are more samples, this many random samples
+    /// of each measure are kept for the median, plots and raw files.
+    pub max_samples_stored: Option<usize>,
+    pub outlier_min_n: usize,
+    pub cv_warn: f64,
+    pub cv_bad: f64,
+    pub sort: SortKey,
+    pub sort_desc: bool,
+    pub alpha: f64,
+    pub rolling_mean: Option<usize>,
+    pub table_style: TableStyle,
+    /// Colors for good and bad results.
+    pub palette: Palette,
+    /// Plot each experiment's distribution as fractions of its own samples.
+    pub normalize_hist: bool,
+    /// Bin distribution plots by the log of the value, for heavy-tailed samples.
+    pub log_scale: bool,
+    pub color: ColorChoice,
+    pub log_format: LogFormat,
+    /// What is printed to stderr, see [`Verbosity`].
+    pub verbosity: Verbosity,
+    /// Called after each successful measured run.
+    pub on_run: Option<OnRun>,
+}
+
+/// Callback invoked after each successful measured run.
+pub type OnRun = Box<dyn Fn(&CompletedRun)>;
+
+/// Values measured by a successful run, passed to [`RunConfig::on_run`].
+pub struct CompletedRun {
+    pub experiment: ExperimentName,
+    /// Number of successful runs of this experiment so far, including ignored ones.
+    pub iteration: usize,
+    pub wall_time: Duration,
+    /// Mean wall time of the experiment's measured runs so far, including this one.
+    pub mean_wall_time: Duration,
+    /// Not reported on some platforms.
+    pub max_rss: Option<MemUsage>,
+    pub metric: Option<u64>,
+}
+
+/// Same defaults as the command line.
+impl Default for RunConfig {
+    fn default() -> RunConfig {
+        let render_options = RenderOptions::default();
+        RunConfig {
+            a: String::new(),
+            b: None,
+            c: None,
+            d: None,
+            e: None,
+            aw: None,
+            bw: None,
+            cw: None,
+            dw: None,
+            ew: None,
+            name_a: None,
+            name_b: None,
+            name_c: None,
+            name_d: None,
+            name_e: None,
+            warmup_runs: 1,
+            warmup_time: Duration::default(),
+            global_warmup: None,
+            warmup_only: false,
+            between: None,
+            random_order: false,
+            replay_order: None,
+            order: None,
+            ignore_first: 0,
+            iterations: None,
+            min_iterations: 0,
+            prompt: false,
+            auto: None,
+            inner_runs: [1; ExperimentName::COUNT],
+            mem: false,
+            mem_experiments: Vec::new(),
+            avg_rss: false,
+            metric: None,
+            metric_name: None,
+            metric_unit: None,
+            metric_direction: Direction::Neutral,
+            metric_offset: 0,
+            metric_scope: MetricScope::Run,
+            metric_file: None,
+            metric_file_delete: false,
+            metric_sanity: None,
+            work_units: None,
+            time_offset: Duration::default(),
+            isolate: false,
+            preflight: true,
+            env: Vec::new(),
+            stdin_file: None,
+            capture: false,
+            fail_output_lines: 20,
+            strict: false,
+            max_duration: None,
+            retries: 0,
+            keep_going: false,
+            wait_for: None,
+            timeout: None,
+            timeout_signal: Signal::KILL,
+            timeout_grace: Duration::from_millis(5000),
+            load: None,
+            window: Duration::from_millis(10_000),
+            time_unit: None,
+            sigfigs: None,
+            no_thousands: false,
+            log_full_graph: false,
+            raw_timestamps: false,
+            raw_labels: false,
+            raw_only: false,
+            no_plots: false,
+            graph_svg: true,
+            report_every: 1,
+            html: None,
+            prometheus: None,
+            export_measures: Vec::new(),
+            tap: false,
+            oneline: None,
+            reference: ExperimentName::A,
+            targets: Vec::new(),
+            fail_if_faster: None,
+            fail_if_worse: Vec::new(),
+            score: None,
+            cool_to_idle: false,
+            idle_load: 1.0,
+            idle_temp: 60.0,
+            idle_max_wait: Duration::from_millis(60_000),
+            drop_outliers: false,
+            max_samples_stored: None,
+            outlier_min_n: 10,
+            cv_warn: render_options.cv_warn,
+            cv_bad: render_options.cv_bad,
+            sort: render_options.sort,
+            sort_desc: render_options.sort_desc,
+            alpha: render_options.alpha,
+            rolling_mean: render_options.rolling_mean,
+            table_style: render_options.table_style,
+            palette: render_options.palette,
+            normalize_hist: render_options.normalize_hist,
+            log_scale: render_options.log_scale,
+            color: ColorChoice::Auto,
+            log_format: LogFormat::Text,
+            verbosity: Verbosity::Normal,
+            on_run: None,
+        }
+    }
+}
+
+/// Error caused by a failing script rather than by absh itself.
+#[derive(Debug)]
+pub struct ScriptFailed(pub String);
+
+impl fmt::Display for ScriptFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ScriptFailed {}
+
+/// Samples needed before comparing a duration with the median.
+const CLOCK_ANOMALY_MIN_SAMPLES: usize = 5;
+/// Duration this many times the median is assumed to be a clock jump.
+const CLOCK_ANOMALY_FACTOR: u64 = 1000;
+
+/// Describe why a measured duration of `inner_runs` runs is implausible,
+/// e.g. after a VM clock jump.
+fn clock_anomaly(
+    elapsed: Duration,
+    offset: Duration,
+    inner_runs: u32,
+    durations: &Numbers,
+) -> Option<String> {
+    if elapsed < offset {
+        return Some(format!("shorter than time offset {}", offset));
+    }
+    if durations.len() < CLOCK_ANOMALY_MIN_SAMPLES {
+        return None;
+    }
+    let med = durations.med()?;
+    let duration = (elapsed.nanos() - offset.nanos()) / inner_runs as u64;
+    if med != 0 && duration / med >= CLOCK_ANOMALY_FACTOR {
+        return Some(format!(
+            "over {} times the median {}",
+            CLOCK_ANOMALY_FACTOR,
+            Duration::from_nanos(med)
+        ));
+    }
+    None
+}
+
+/// Wall clock time exceeding monotonic time by more than this means the machine slept.
+const SLEEP_MIN_GAP: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Time the machine was suspended during a run, if noticeable.
+///
+/// Monotonic clock does not advance during suspend, but wall clock does.
+fn slept(monotonic: std::time::Duration, started_at: SystemTime) -> Option<std::time::Duration> {
+    // Wall clock stepped back, e.g. by NTP, is not a sleep.
+    let wall = SystemTime::now().duration_since(started_at).ok()?;
+    let gap = wall.checked_sub(monotonic)?;
+    if gap > SLEEP_MIN_GAP { Some(gap) } else { None }
+}
+
+/// Remember the failure, or abort in strict mode.
+fn record_failure(
+    log: &mut RunLog,
+    test: &mut Experiment,
+    failure: String,
+    strict: bool,
+) -> anyhow::Result<()> {
+    log.event(&Event::RunFailed {
+        experiment: test.name,
+        error: &failure,
+    })?;
+    if strict {
+        return Err(ScriptFailed(format!("{}: {}", test.label, failure)).into());
+    }
+    test.last_failure = Some(failure);
+    Ok(())
+}
+
+/// Abort if an experiment has not succeeded after this many attempts.
+const ATTEMPTS_BEFORE_GIVING_UP: usize = 5;
+
+/// Why the main loop stopped.
+pub enum StopReason {
+    Iterations(u32),
+    MaxDuration(Duration),
+    Signal(Signal),
+    /// All experiments reached `auto` precision.
+    Converged(f64),
+    /// Only warmup scripts were run, see `RunConfig::warmup_only`.
+    WarmupOnly,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Iterations(n) => write!(f, "reached {} iterations", n),
+            StopReason::MaxDuration(d) => write!(f, "exceeded max duration of {}", d),
+            StopReason::Signal(s) => write!(f, "received {}", s),
+            StopReason::Converged(p) => {
+                write!(f, "mean of every experiment is known within {}%", p)
+            }
+            StopReason::WarmupOnly => write!(f, "ran warmup scripts only"),
+        }
+    }
+}
+
+impl RunConfig {
+    fn timeout(&self) -> Option<Timeout> {
+        if self.load.is_some() {
+            // Load script is stopped when the window ends.
+            return Some(Timeout {
+                duration: self.window,
+                signal: Signal::TERM,
+                grace: self.timeout_grace,
+            });
+        }
+        self.timeout.map(|duration| Timeout {
+            duration,
+            signal: self.timeout_signal,
+            grace: self.timeout_grace,
+        })
+    }
+
+    fn idle_thresholds(&self) -> Option<IdleThresholds> {
+        if !self.cool_to_idle {
+            return None;
+        }
+        Some(IdleThresholds {
+            load: self.idle_load,
+            temp_celsius: self.idle_temp,
+            max_wait: self.idle_max_wait,
+        })
+    }
+
+    fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            include_distr: true,
+            cv_warn: self.cv_warn,
+            cv_bad: self.cv_bad,
+            sort: self.sort,
+            sort_desc: self.sort_desc,
+            alpha: self.alpha,
+            rolling_mean: self.rolling_mean,
+            table_style: self.table_style,
+            palette: self.palette,
+            normalize_hist: self.normalize_hist,
+            log_scale: self.log_scale,
+        }
+    }
+
+    fn spawn_options(&self, process_group: bool) -> SpawnOptions {
+        SpawnOptions {
+            process_group,
+            isolate: self.isolate,
+            env: self.env.clone(),
+            stdin: None,
+            capture: false,
+        }
+    }
+
+    /// Options for warmup scripts.
+    fn warmup_spawn_options(&self) -> SpawnOptions {
+        SpawnOptions {
+            capture: self.capture,
+            ..self.spawn_options(false)
+        }
+    }
+
+    fn run_spawn_options(&self) -> SpawnOptions {
+        SpawnOptions {
+            stdin: self.stdin_file.clone(),
+            ..self.spawn_options(self.timeout.is_some() || self.load.is_some() || self.avg_rss)
+        }
+    }
+
+    /// Whether the user defined metric is recorded, from a script or a file.
+    fn records_metric(&self) -> bool {
+        self.metric.is_some() || self.metric_file.is_some()
+    }
+
+    /// Options for the script whose run is measured.
+    fn measured_spawn_options(&self) -> SpawnOptions {
+        let options = match self.load {
+            Some(_) => self.spawn_options(true),
+            None => self.run_spawn_options(),
+        };
+        SpawnOptions {
+            capture: self.capture,
+            ..options
+        }
+    }
+}
+
+/// When the metric script runs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum MetricScope {
+    /// After each run, the output is the sample.
+    Run,
+    /// After all experiments of a pair ran. The output is a cumulative counter, and its
+    /// increment is split between the experiments in proportion to their wall time.
+    Pair,
+    /// Started together with each run, with `ABSH_RUN_PID` set to the pid of the run
+    /// shell, so it can read counters of the live process. The sample is its output,
+    /// read after both the run and the metric script exit.
+    During,
+}
+
+/// Environment variable with the pid of the run shell for `MetricScope::During`.
+const RUN_PID_ENV: &str = "ABSH_RUN_PID";
+
+fn run_metric(log: &mut RunLog, metric: &str) -> anyhow::Result<Option<u64>> {
+    parse_metric(log, sh_stdout(metric))
+}
+
+/// Start the metric script concurrently with the run script `pid`.
+fn start_metric_during(metric: &str, pid: u32) -> JoinHandle<anyhow::Result<String>> {
+    let metric = metric.to_owned();
+    thread::spawn(move || sh_stdout_with_env(&metric, &[(RUN_PID_ENV, pid.to_string())]))
+}
+
+fn parse_metric(log: &mut RunLog, stdout: anyhow::Result<String>) -> anyhow::Result<Option<u64>> {
+    match stdout {
+        Ok(stdout) => parse_metric_number(log, "metric script output", &stdout),
+        Err(e) => {
+            writeln!(log.both_log_and_stderr(), "metric script failed: {}", e)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Read the metric written by the run script, missing file is not fatal
+/// like a failed metric script.
+fn read_metric_file(log: &mut RunLog, path: &Path, delete: bool) -> anyhow::Result<Option<u64>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            writeln!(
+                log.both_log_and_stderr(),
+                "failed to read metric file {}: {}",
+                path.display(),
+                e
+            )?;
+            return Ok(None);
+        }
+    };
+    if delete {
+        if let Err(e) = fs::remove_file(path) {
+            writeln!(
+                log.both_log_and_stderr(),
+                "failed to remove metric file {}: {}",
+                path.display(),
+                e
+            )?;
+        }
+    }
+    parse_metric_number(log, "metric file content", &content)
+}
+
+fn parse_metric_number(log: &mut RunLog, what: &str, s: &str) -> anyhow::Result<Option<u64>> {
+    match s.trim().parse::<u64>() {
+        Ok(value) => Ok(Some(value)),
+        Err(_) => {
+            writeln!(
+                log.both_log_and_stderr(),
+                "{} is not a number: {:?}",
+                what,
+                s.trim()
+            )?;
+            Ok(None)
+        }
+    }
+}
+
+/// Samples kept per measure with `--streaming`.
+pub const STREAMING_RESERVOIR_SIZE: usize = 10_000;
+
+/// How often RSS is sampled with `avg_rss`.
+const RSS_SAMPLE_INTERVAL_MILLIS: u64 = 10;
+
+/// How often `wait_for` file is checked.
+const WAIT_FOR_POLL_MILLIS: u64 = 1;
+
+/// Poll until the file exists, false if `deadline` passed or shutdown is requested first.
+fn wait_for_file(path: &Path, deadline: Option<Instant>) -> bool {
+    loop {
+        if path.exists() {
+            return true;
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) || shutdown::requested().is_some() {
+            return false;
+        }
+        thread::sleep(std::time::Duration::from_millis(WAIT_FOR_POLL_MILLIS));
+    }
+}
+
+/// Print captured output of a failed script.
+fn write_failure_output(log: &mut RunLog, output: &[String]) -> anyhow::Result<()> {
+    if output.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        log.both_log_and_stderr(),
+        "last {} lines of output:",
+        output.len()
+    )?;
+    for line in output {
+        writeln!(log.both_log_and_stderr(), "    {}", line)?;
+    }
+    Ok(())
+}
+
+/// Warn if the metric sample is implausibly far from the running median.
+fn check_metric_sanity(
+    log: &mut RunLog,
+    test: &Experiment,
+    metric: u64,
+    factor: f64,
+) -> anyhow::Result<()> {
+    let samples = &test.measures[MeasureKey::UserDefinedMetric];
+    // Median of very few samples is not a useful reference.
+    if samples.len() < 3 {
+        return Ok(());
+    }
+    let median = samples.med().unwrap() as f64;
+    if median == 0.0 {
+        return Ok(());
+    }
+    let sample = metric as f64;
+    if sample > median * factor || sample * factor < median {
+        writeln!(
+            log.both_log_and_stderr(),
+            "{red}suspicious metric sample {} of {}: more than {}x away from running median {}; \
+                check the metric script output{reset}",
+            metric,
+            test.name_colored(),
+            factor,
+            median,
+            red = ansi::RED,
+            reset = ansi::RESET,
+        )?;
+    }
+    Ok(())
+}
+
+fn cool_to_idle(log: &mut RunLog, thresholds: &IdleThresholds) -> anyhow::Result<()> {
+    let wait = wait_for_idle(thresholds);
+    let load = match wait.load {
+        Some(load) => format!("load {:.2}", load),
+        None => "load unknown".to_owned(),
+    };
+    let temp = match wait.temp_celsius {
+        Some(temp) => format!(", temp {:.0} C", temp),
+        None => String::new(),
+    };
+    if !wait.idle {
+        writeln!(
+            log.both_log_and_stderr(),
+            "{yellow}not idle after {:.1} s ({}{}), running anyway{reset}",
+            wait.waited.as_secs_f64(),
+            load,
+            temp,
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+    } else if wait.waited.as_millis() != 0 {
+        writeln!(
+            log.both_log_and_stderr(),
+            "cooled down in {:.1} s ({}{})",
+            wait.waited.as_secs_f64(),
+            load,
+            temp,
+        )?;
+    }
+    Ok(())
+}
+
+/// Environment variable with the path of a directory created for each run,
+/// shared by its warmup and run scripts and removed afterwards.
+pub const TMPDIR_ENV: &str = "ABSH_TMPDIR";
+
+/// Run the warmup script, repeated for at least `warmup_runs` and at least `warmup_time`
+/// if not empty. Returns time spent, or the failure.
+fn run_warmup(
+    log: &mut RunLog,
+    config: &RunConfig,
+    test: &Experiment,
+    spawn_options: &SpawnOptions,
+) -> anyhow::Result<Result<Duration, String>> {
+    let warmup_start = Instant::now();
+    let mut warmup_count = 0;
+    loop {
+        let mut process = spawn_sh(&test.warmup, spawn_options)?;
+        let capture = Capture::start(&mut process, config.fail_output_lines);
+        let status = process.wait4()?;
+        let output = capture.map(Capture::finish).unwrap_or_default();
+        if !status.status.success() {
+            let failure = format!("warmup failed: {}", status.status);
+            writeln!(log.both_log_and_stderr(), "{}", failure)?;
+            write_failure_output(log, &output)?;
+            return Ok(Err(failure));
+        }
+        warmup_count += 1;
+        let warmup_elapsed = Duration::from_std(warmup_start.elapsed()).nanos();
+        if test.warmup.is_empty()
+            || warmup_count >= config.warmup_runs && warmup_elapsed >= config.warmup_time.nanos()
+            || shutdown::requested().is_some()
+        {
+            break;
+        }
+    }
+    let warmup_time = Duration::from_std(warmup_start.elapsed());
+    if warmup_count > 1 {
+        writeln!(
+            log.at(Verbosity::Normal),
+            "warmup ran {} times in {}",
+            warmup_count,
+            warmup_time,
+        )?;
+    } else if !test.warmup.is_empty() {
+        writeln!(log.at(Verbosity::Normal), "warmup took {}", warmup_time)?;
+    }
+    Ok(Ok(warmup_time))
+}
+
+/// Run each non-empty warmup script, for `warmup_only`.
+fn run_warmups_only(
+    log: &mut RunLog,
+    config: &RunConfig,
+    experiments: &ExperimentMap<Experiment>,
+) -> anyhow::Result<()> {
+    let mut failed = Vec::new();
+    for test in experiments.values() {
+        if test.warmup.is_empty() {
+            continue;
+        }
+        writeln!(log.both_log_and_stderr())?;
+        writeln!(
+            log.both_log_and_stderr(),
+            "running warmup of {}:",
+            test.name_colored()
+        )?;
+        for line in test.warmup.lines() {
+            writeln!(log.both_log_and_stderr(), "    {}", line)?;
+        }
+        let temp_dir = TempDir::new()?;
+        let tmpdir = temp_dir
+            .path()
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("temp dir path is not UTF-8"))?;
+        let spawn_options = config.warmup_spawn_options().with_env(TMPDIR_ENV, tmpdir);
+        if run_warmup(log, config, test, &spawn_options)?.is_err() {
+            failed.push(test.name.name());
+        }
+    }
+    writeln!(log.both_log_and_stderr())?;
+    if !failed.is_empty() {
+        return Err(ScriptFailed(format!("warmup failed: {}", failed.join(", "))).into());
+    }
+    writeln!(log.both_log_and_stderr(), "All warmups succeeded.")?;
+    Ok(())
+}
+
+fn run_test(log: &mut RunLog, config: &RunConfig, test: &mut Experiment) -> anyhow::Result<()> {
+    writeln!(log.at(Verbosity::Normal))?;
+    if let Some(thresholds) = config.idle_thresholds() {
+        cool_to_idle(log, &thresholds)?;
+    }
+    writeln!(
+        log.at(Verbosity::Normal),
+        "running test: {}",
+        test.name_colored()
+    )?;
+    let warmup_lines = test.warmup.lines().collect::<Vec<_>>();
+    if !warmup_lines.is_empty() {
+        writeln!(log.at(Verbosity::Normal), "running warmup script:")?;
+        for line in &warmup_lines {
+            writeln!(log.at(Verbosity::Normal), "    {}", line)?;
+        }
+    }
+
+    test.attempts += 1;
+
+    // Shared by warmup and run, e.g. for a cache built by warmup.
+    let temp_dir = TempDir::new()?;
+    let tmpdir = temp_dir
+        .path()
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("temp dir path is not UTF-8"))?;
+    let warmup_spawn_options = config.warmup_spawn_options().with_env(TMPDIR_ENV, tmpdir);
+
+    let warmup_time = match run_warmup(log, config, test, &warmup_spawn_options)? {
+        Ok(warmup_time) => warmup_time,
+        Err(failure) => return record_failure(log, test, failure, config.strict),
+    };
+
+    writeln!(log.at(Verbosity::Normal), "running script:")?;
+    let lines = test.run.lines().collect::<Vec<_>>();
+    for line in &lines {
+        writeln!(log.at(Verbosity::Normal), "    {}", line)?;
+    }
+
+    // In load mode the run script is a server, and the load script is measured instead.
+    let mut server = match &config.load {
+        Some(load) => {
+            writeln!(log.at(Verbosity::Normal), "running load script:")?;
+            for line in load.lines() {
+                writeln!(log.at(Verbosity::Normal), "    {}", line)?;
+            }
+            Some(Server::start(
+                &test.run,
+                &config.run_spawn_options().with_env(TMPDIR_ENV, tmpdir),
+                config.timeout_grace,
+            )?)
+        }
+        None => None,
+    };
+    let inner_runs = config.inner_runs[test.name.index()];
+    let repeated;
+    let script = match &config.load {
+        Some(load) => load.as_str(),
+        None if inner_runs > 1 => {
+            repeated = repeat_script(&test.run, inner_runs);
+            &repeated
+        }
+        None => &test.run,
+    };
+
+    let timeout = config.timeout();
+    let spawn_options = config.measured_spawn_options().with_env(TMPDIR_ENV, tmpdir);
+    if log.shows(Verbosity::Verbose) {
+        let cwd = env::current_dir()?;
+        writeln!(log.at(Verbosity::Verbose), "cwd: {}", cwd.display())?;
+        writeln!(
+            log.at(Verbosity::Verbose),
+            "command: {}",
+            command_line(script, &spawn_options)
+        )?;
+    }
+
+    log.event(&Event::RunStart {
+        experiment: test.name,
+    })?;
+    let mut attempt = 0;
+    let (status, duration, avg_rss, started_at, slept, metric_during) = loop {
+        if let Some(path) = &config.wait_for {
+            // Left by the previous run.
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(anyhow::anyhow!(
+                        "failed to remove {}: {}",
+                        path.display(),
+                        e
+                    ));
+                }
+                _ => {}
+            }
+        }
+        let started_at = SystemTime::now();
+        let start = Instant::now();
+
+        let mut process = spawn_sh(script, &spawn_options)?;
+        let metric_during = match &config.metric {
+            Some(metric) if config.metric_scope == MetricScope::During => {
+                Some(start_metric_during(metric, process.id()))
+            }
+            _ => None,
+        };
+        let watchdog = timeout.as_ref().map(|t| Watchdog::start(&process, t));
+        let sampler = if config.avg_rss {
+            Some(RssSampler::start(
+                process.id(),
+                Duration::from_millis(RSS_SAMPLE_INTERVAL_MILLIS),
+            ))
+        } else {
+            None
+        };
+        let capture = Capture::start(&mut process, config.fail_output_lines);
+        let status = process.wait4()?;
+        let waited = match &config.wait_for {
+            Some(path) if status.status.success() => wait_for_file(
+                path,
+                config
+                    .timeout
+                    .map(|t| start + std::time::Duration::from_nanos(t.nanos())),
+            ),
+            _ => true,
+        };
+        let elapsed = start.elapsed();
+        let slept = slept(elapsed, started_at);
+        let output = capture.map(Capture::finish).unwrap_or_default();
+        let avg_rss = sampler.and_then(RssSampler::finish);
+        let timed_out = match watchdog {
+            Some(watchdog) => watchdog.finish(),
+            None => false,
+        };
+        // Load script running until the end of the window is expected.
+        let window_ended = timed_out && config.load.is_some();
+        if status.status.success() && !timed_out && waited || window_ended {
+            break (
+                status,
+                Duration::from_std(elapsed),
+                avg_rss,
+                started_at,
+                slept,
+                metric_during,
+            );
+        }
+
+        let failure = if !waited {
+            format!(
+                "{} did not appear after the script exited",
+                config.wait_for.as_ref().unwrap().display()
+            )
+        } else if timed_out {
+            format!(
+                "script timed out after {}: {}",
+                config.timeout.unwrap(),
+                status.status
+            )
+        } else {
+            format!("script failed: {}", status.status)
+        };
+        // Not to overlap with the next attempt.
+        if let Some(metric_during) = metric_during {
+            let _ = metric_during.join();
+        }
+        // Most likely the signal killed the script too, so it is not the script to blame.
+        if let Some(signal) = shutdown::requested() {
+            writeln!(
+                log.both_log_and_stderr(),
+                "{}, discarding sample: received {}",
+                failure,
+                signal
+            )?;
+            return Ok(());
+        }
+        if attempt == config.retries {
+            writeln!(log.both_log_and_stderr(), "{}", failure)?;
+            write_failure_output(log, &output)?;
+            return record_failure(log, test, failure, config.strict);
+        }
+        attempt += 1;
+        writeln!(
+            log.both_log_and_stderr(),
+            "{}, retrying ({}/{})",
+            failure,
+            attempt,
+            config.retries
+        )?;
+        write_failure_output(log, &output)?;
+    };
+
+    if let Some(exit_status) = server.as_mut().map(|s| s.exited()).transpose()?.flatten() {
+        let failure = format!("server exited during load: {}", exit_status);
+        if let Some(metric_during) = metric_during {
+            let _ = metric_during.join();
+        }
+        writeln!(log.both_log_and_stderr(), "{}", failure)?;
+        return record_failure(log, test, failure, config.strict);
+    }
+
+    let max_rss = MemUsage::from_bytes(status.rusage.maxrss);
+    if let Some(slept) = slept {
+        writeln!(
+            log.both_log_and_stderr(),
+            "{yellow}discarding sample of {}: machine slept for {}{reset}",
+            duration,
+            Duration::from_std(slept),
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+        if let Some(metric_during) = metric_during {
+            let _ = metric_during.join();
+        }
+        test.sleep_discards += 1;
+        test.last_failure = Some("machine slept during the run".to_owned());
+        return Ok(());
+    }
+    if let Some(anomaly) = clock_anomaly(
+        duration,
+        config.time_offset,
+        inner_runs,
+        &test.measures[MeasureKey::WallTime],
+    ) {
+        writeln!(
+            log.both_log_and_stderr(),
+            "{yellow}discarding sample of {}: {}{reset}",
+            duration,
+            anomaly,
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+        if let Some(metric_during) = metric_during {
+            let _ = metric_during.join();
+        }
+        test.clock_anomalies += 1;
+        test.last_failure = Some(format!("clock anomaly: {}", anomaly));
+        return Ok(());
+    }
+    // Offset is of the whole spawn, not of each inner run.
+    let duration =
+        Duration::from_nanos(duration.nanos().saturating_sub(config.time_offset.nanos()))
+            / inner_runs as u64;
+
+    let metric = match (&config.metric, &config.metric_file) {
+        (Some(metric), _) if config.metric_scope == MetricScope::Run => run_metric(log, metric)?,
+        (_, Some(path)) => read_metric_file(log, path, config.metric_file_delete)?,
+        _ => match metric_during {
+            Some(metric_during) => parse_metric(
+                log,
+                metric_during
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("metric thread panicked"))),
+            )?,
+            None => None,
+        },
+    }
+    .map(|m| m.saturating_sub(config.metric_offset));
+    drop(server);
+    if let (Some(metric), Some(factor)) = (metric, config.metric_sanity) {
+        check_metric_sanity(log, test, metric, factor)?;
+    }
+
+    let metric_str = match metric {
+        Some(metric) => format!(", metric {}", metric),
+        None => String::new(),
+    };
+    // Max RSS is not reported on some platforms, see `max_rss_available`.
+    let max_rss_str = match avg_rss {
+        Some(avg_rss) if status.rusage.maxrss != 0 => format!(
+            ", avg rss {} MiB, peak {} MiB",
+            avg_rss.mib(),
+            max_rss.mib()
+        ),
+        _ if status.rusage.maxrss != 0 => format!(", max rss {} MiB", max_rss.mib()),
+        _ => String::new(),
+    };
+    writeln!(
+        log.at(Verbosity::Normal),
+        "{} finished in {}{}{}{}",
+        test.name_colored(),
+        duration
+            .display_in(config.time_unit)
+            .with_sigfigs(config.sigfigs),
+        if inner_runs > 1 {
+            format!(" per inner run of {}", inner_runs)
+        } else {
+            String::new()
+        },
+        max_rss_str,
+        metric_str,
+    )?;
+
+    test.successes += 1;
+    let timestamp = started_at
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+    let mut samples = vec![(MeasureKey::WallTime, duration.nanos())];
+    if status.rusage.maxrss != 0 && test.records(MeasureKey::MaxRss) {
+        samples.push((MeasureKey::MaxRss, max_rss.bytes()));
+    }
+    if let Some(metric) = metric {
+        samples.push((MeasureKey::UserDefinedMetric, metric));
+    }
+    if let (Some(units), true) = (config.work_units, duration.nanos() != 0) {
+        // Thousandths of units per second.
+        let throughput = units as u128 * 1_000_000_000_000 / duration.nanos() as u128;
+        samples.push((
+            MeasureKey::Throughput,
+            u64::try_from(throughput).unwrap_or(u64::MAX),
+        ));
+    }
+    for &(key, value) in &samples {
+        let index = test.push(key, value, timestamp);
+        if let (MeasureKey::WallTime, Some(index)) = (key, index) {
+            set_or_push(&mut test.warmup_times, index, warmup_time.nanos());
+        }
+    }
+    writeln!(
+        log.at(Verbosity::Debug),
+        "{} sample: {}",
+        test.name_colored(),
+        samples
+            .iter()
+            .map(|(key, value)| format!("{}={}", key.id(), value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    )?;
+    log.event(&Event::RunResult {
+        experiment: test.name,
+        wall_time_ns: duration.nanos(),
+        max_rss_bytes: Some(max_rss.bytes()).filter(|_| status.rusage.maxrss != 0),
+        metric,
+    })?;
+    if let Some(on_run) = &config.on_run {
+        on_run(&CompletedRun {
+            experiment: test.name,
+            iteration: test.successes,
+            wall_time: duration,
+            mean_wall_time: Duration::from_nanos(
+                test.measures[MeasureKey::WallTime].mean().unwrap(),
+            ),
+            max_rss: Some(max_rss).filter(|_| status.rusage.maxrss != 0),
+            metric,
+        });
+    }
+    Ok(())
+}
+
+/// Ask on the terminal how many more iterations to run, `None` to stop.
+fn prompt_more_iterations() -> anyhow::Result<Option<u32>> {
+    loop {
+        write_or_exit(
+            &mut io::stderr(),
+            "Run more iterations? Enter a number, or press Enter to stop: ",
+        )?;
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 || shutdown::requested().is_some() {
+            return Ok(None);
+        }
+        match line.trim() {
+            "" => return Ok(None),
+            more => match more.parse::<u32>() {
+                Ok(more) if more != 0 => return Ok(Some(more)),
+                _ => write_or_exit(
+                    &mut io::stderr(),
+                    &format!("expecting a positive number: {:?}\n", more),
+                )?,
+            },
+        }
+    }
+}
+
+/// Run the `--between` script, warning if it fails.
+fn run_between(log: &mut RunLog, config: &RunConfig) -> anyhow::Result<()> {
+    let between = match &config.between {
+        Some(between) => between,
+        None => return Ok(()),
+    };
+    let status = spawn_sh(between, &config.spawn_options(false))?.wait()?;
+    if !status.success() {
+        writeln!(
+            log.both_log_and_stderr(),
+            "{yellow}between script failed: {}{reset}",
+            status,
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+    }
+    Ok(())
+}
+
+fn run_pair(
+    log: &mut RunLog,
+    config: &RunConfig,
+    order: &mut PairOrder,
+    last_metric: &mut Option<u64>,
+    tests: &mut ExperimentMap<Experiment>,
+) -> anyhow::Result<()> {
+    let names: Vec<ExperimentName> = tests.keys().collect();
+    let (indices, replay_exhausted) = order.next(&names);
+    if replay_exhausted {
+        writeln!(
+            log.both_log_and_stderr(),
+            "{yellow}all orders from --replay-order are used, continuing with {} order{reset}",
+            if config.random_order {
+                "random"
+            } else {
+                "fixed"
+            },
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+    }
+    if order.recorded() {
+        log.append_order(&format_order(&indices))?;
+    }
+    let runs_before: ExperimentMap<usize> = tests.map(|t| t.runs());
+    for &index in &indices {
+        let test = tests.get_mut(index).unwrap();
+        if auto_precision(config, test).is_some() {
+            continue;
+        }
+        run_test(log, config, test)?;
+        run_between(log, config)?;
+        if let Some(precision) = auto_precision(config, test) {
+            writeln!(
+                log.at(Verbosity::Normal),
+                "{green}{} converged: mean ±{:.2}% after {} runs, not running it anymore{reset}",
+                test.name_colored(),
+                precision,
+                test.runs(),
+                green = config.palette.good(),
+                reset = ansi::RESET,
+            )?;
+        }
+        if shutdown::requested().is_some() {
+            return Ok(());
+        }
+    }
+    if config.metric_scope == MetricScope::Pair {
+        if let Some(metric) = &config.metric {
+            let reading = run_metric(log, metric)?;
+            if let (Some(prev), Some(reading)) = (*last_metric, reading) {
+                let delta = reading
+                    .saturating_sub(prev)
+                    .saturating_sub(config.metric_offset);
+                record_pair_metric(log, config, tests, &runs_before, delta)?;
+            }
+            *last_metric = reading;
+        }
+    }
+    if !config.keep_going {
+        for test in tests.values() {
+            if test.successes == 0 && test.attempts >= ATTEMPTS_BEFORE_GIVING_UP {
+                return Err(ScriptFailed(format!(
+                    "experiment {} failed all {} attempts, last failure: {}; \
+                    use --keep-going to continue anyway",
+                    test.label,
+                    test.attempts,
+                    test.last_failure.as_deref().unwrap_or("unknown"),
+                ))
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Samples needed before `auto` trusts the confidence interval.
+const AUTO_MIN_RUNS: usize = 5;
+
+/// Confidence interval half-width of the wall time mean in percent,
+/// if it is within the `auto` target.
+fn auto_precision(config: &RunConfig, test: &Experiment) -> Option<f64> {
+    let target = config.auto?;
+    let numbers = &test.measures[MeasureKey::WallTime];
+    if numbers.len() < AUTO_MIN_RUNS || test.runs() < config.min_iterations as usize {
+        return None;
+    }
+    let stats = numbers.stats()?;
+    if stats.mean == 0 {
+        return None;
+    }
+    let precision = stats.mean_conf_h() * 100.0 / stats.mean as f64;
+    Some(precision).filter(|&p| p <= target)
+}
+
+/// Split the metric increment over a pair between experiments by their wall time.
+fn record_pair_metric(
+    log: &mut RunLog,
+    config: &RunConfig,
+    tests: &mut ExperimentMap<Experiment>,
+    runs_before: &ExperimentMap<usize>,
+    delta: u64,
+) -> anyhow::Result<()> {
+    // Failed runs recorded no time, and get no share.
+    let durations: Vec<(ExperimentName, u64)> = tests
+        .iter()
+        .filter(|(name, t)| t.runs() > runs_before[*name])
+        .map(|(name, t)| (name, t.measures[MeasureKey::WallTime].last().unwrap()))
+        .collect();
+    let total: u128 = durations.iter().map(|&(_, d)| d as u128).sum();
+    if total == 0 {
+        return Ok(());
+    }
+    writeln!(log.at(Verbosity::Normal), "pair metric {}", delta)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+    for (name, duration) in durations {
+        let share = (delta as u128 * duration as u128 / total) as u64;
+        if let Some(factor) = config.metric_sanity {
+            check_metric_sanity(log, &tests[name], share, factor)?;
+        }
+        tests[name].push(MeasureKey::UserDefinedMetric, share, timestamp);
+    }
+    Ok(())
+}
+
+/// Experiments with outliers dropped if requested.
+fn without_outliers(
+    config: &RunConfig,
+    experiments: &ExperimentMap<Experiment>,
+) -> Option<ExperimentMap<Experiment>> {
+    if !config.drop_outliers {
+        return None;
+    }
+    Some(experiments.map(|t| t.without_outliers(config.outlier_min_n)))
+}
+
+/// Change of the mean below this percent is shown as converging.
+const MEAN_DELTA_CONVERGED_PERCENT: f64 = 0.1;
+
+/// Print the change of each mean since the previous render, and remember the means.
+fn write_mean_deltas(
+    log: &mut RunLog,
+    config: &RunConfig,
+    measures: &AllMeasures,
+    summary: &SummaryReport,
+    previous_means: &mut MeasureMap<ExperimentMap<u64>>,
+) -> anyhow::Result<()> {
+    for measure_summary in &summary.measures {
+        let measure = measures.get(measure_summary.key).unwrap();
+        let previous = &previous_means[measure_summary.key];
+        let deltas: Vec<String> = measure_summary
+            .stats
+            .iter()
+            .filter_map(|(name, stats)| {
+                let previous = *previous.get(name)?;
+                if previous == 0 {
+                    return None;
+                }
+                let percent = (stats.mean as f64 / previous as f64 - 1.0) * 100.0;
+                let color = if percent.abs() < MEAN_DELTA_CONVERGED_PERCENT {
+                    config.palette.good()
+                } else {
+                    ""
+                };
+                Some(format!(
+                    "{} mean {} ({}{:+.1}%{} since last update)",
+                    name.colored(summary.label(name)),
+                    measure.format_number(stats.mean),
+                    color,
+                    percent,
+                    if color.is_empty() { "" } else { ansi::RESET },
+                ))
+            })
+            .collect();
+        if !deltas.is_empty() {
+            writeln!(
+                log.at(Verbosity::Normal),
+                "{}: {}",
+                measure.name(),
+                deltas.join(", ")
+            )?;
+        }
+        previous_means[measure_summary.key] = measure_summary.stats.map(|s| s.mean);
+    }
+    Ok(())
+}
+
+/// Print stats if verbosity is at least `level`, and the change of means since
+/// the previous call.
+fn render_stats(
+    log: &mut RunLog,
+    config: &RunConfig,
+    measures: &AllMeasures,
+    all_experiments: &ExperimentMap<Experiment>,
+    previous_means: &mut MeasureMap<ExperimentMap<u64>>,
+    level: Verbosity,
+) -> anyhow::Result<()> {
+    writeln!(log.at(level))?;
+
+    let trimmed = without_outliers(config, all_experiments);
+    let experiments = trimmed.as_ref().unwrap_or(all_experiments);
+
+    if config.drop_outliers {
+        for test in all_experiments.values() {
+            // Same per measure condition as `Experiment::without_outliers`.
+            for measure in measures.with_stats(all_experiments) {
+                let numbers = &test.measures[measure.key()];
+                if numbers.len() < config.outlier_min_n {
+                    writeln!(
+                        log.at(level),
+                        "{}: {}: outlier removal inactive until {} samples",
+                        test.name_colored(),
+                        measure.name(),
+                        config.outlier_min_n
+                    )?;
+                    continue;
+                }
+                writeln!(
+                    log.at(level),
+                    "{}: {}: outlier removal active",
+                    test.name_colored(),
+                    measure.name()
+                )?;
+                let (_, outliers) = numbers.split_outliers();
+                if outliers.is_empty() {
+                    continue;
+                }
+                writeln!(
+                    log.at(level),
+                    "{}: {}: {} outlier{} excluded ({:.1}%, values: {})",
+                    test.name_colored(),
+                    measure.name(),
+                    outliers.len(),
+                    if outliers.len() == 1 { "" } else { "s" },
+                    outliers.len() as f64 * 100.0 / numbers.len() as f64,
+                    outliers
+                        .iter()
+                        .map(|&n| measure.format_number(n))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )?;
+            }
+        }
+        writeln!(log.at(level))?;
+    }
+
+    for warning in measures.sample_count_warnings(all_experiments) {
+        writeln!(
+            log.at(level),
+            "{yellow}{}{reset}",
+            warning,
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+    }
+
+    let summary = SummaryReport::new(measures, experiments, config.reference);
+    for measure in &summary.measures {
+        log.event(&Event::Stats {
+            measure: measures.get(measure.key).unwrap().id(),
+            stats: &measure.stats,
+        })?;
+    }
+    let render_options = config.render_options();
+    let graph_full = measures.render_stats(experiments, &summary, &render_options)?;
+    let graph_short = measures.render_stats(
+        experiments,
+        &summary,
+        &RenderOptions {
+            include_distr: false,
+            ..render_options
+        },
+    )?;
+
+    if log.shows(level) {
+        let graph = if config.no_plots {
+            &graph_short
+        } else {
+            &graph_full
+        };
+        write!(log.stderr_only(), "{}", graph)?;
+    }
+    if config.log_full_graph {
+        write!(log.log_only(), "{}", graph_full)?;
+    } else {
+        write!(log.log_only(), "{}", graph_short)?;
+    }
+    write_mean_deltas(log, config, measures, &summary, previous_means)?;
+
+    let export_summary = summary.only(&config.export_measures);
+    log.write_graph(
+        &graph_full,
+        &measures.render_stats(experiments, &export_summary, &render_options)?,
+    )?;
+    if config.graph_svg {
+        log.write_graph_svg(&graph_svg(measures, experiments, &export_summary))?;
+    }
+    if let Some(html) = &config.html {
+        write_html_report(
+            html,
+            &RunLog::args_str(),
+            measures,
+            experiments,
+            &export_summary,
+        )?;
+    }
+    if let Some(prometheus) = &config.prometheus {
+        write_prometheus(prometheus, measures, &export_summary)?;
+    }
+
+    write_raw(log, config, measures, all_experiments)
+}
+
+fn write_raw(
+    log: &mut RunLog,
+    config: &RunConfig,
+    measures: &AllMeasures,
+    experiments: &ExperimentMap<Experiment>,
+) -> anyhow::Result<()> {
+    let format = if config.raw_timestamps {
+        RawFormat::Timestamped {
+            labels: config.raw_labels,
+        }
+    } else {
+        RawFormat::Values
+    };
+    measures.write_raw(experiments, log, format)
+}
+
+/// Print whether targets are met, and return true if all are.
+fn check_targets(
+    log: &mut RunLog,
+    targets: &[Target],
+    palette: Palette,
+    measures: &AllMeasures,
+    experiments: &ExperimentMap<Experiment>,
+) -> anyhow::Result<bool> {
+    let mut all_met = true;
+    for target in targets {
+        // Checked when starting.
+        let measure = measures.get(target.key).unwrap();
+        let op = match measure.direction() {
+            Direction::Higher => ">=",
+            _ => "<=",
+        };
+        writeln!(
+            log.both_log_and_stderr(),
+            "Target: {} of {} {} {}",
+            target.stat,
+            measure.name(),
+            op,
+            measure.format_number(target.value),
+        )?;
+        for test in experiments.values() {
+            if !test.records(target.key) {
+                writeln!(log.both_log_and_stderr(), "{}: n/a", test.name_colored())?;
+                continue;
+            }
+            let (actual, pass) = match target.check(&test.measures[target.key], measure.direction())
+            {
+                Some((actual, pass)) => (measure.format_number(actual), pass),
+                None => ("no samples".to_owned(), false),
+            };
+            all_met &= pass;
+            writeln!(
+                log.both_log_and_stderr(),
+                "{}: {} {color}{}{reset}",
+                test.name_colored(),
+                actual,
+                if pass { "pass" } else { "fail" },
+                color = if pass { palette.good() } else { palette.bad() },
+                reset = ansi::RESET,
+            )?;
+        }
+    }
+    Ok(all_met)
+}
+
+/// Print experiments significantly faster than the reference by more than `percent`,
+/// and return true if there are none.
+fn check_fail_if_faster(
+    log: &mut RunLog,
+    percent: f64,
+    palette: Palette,
+    summary: &SummaryReport,
+) -> anyhow::Result<bool> {
+    let time = match summary.measure(MeasureKey::WallTime) {
+        Some(time) => time,
+        None => return Ok(true),
+    };
+    let mut pass = true;
+    for c in &time.comparisons {
+        if c.is_better(Direction::Lower) && c.ratio < 1.0 - percent / 100.0 {
+            pass = false;
+            writeln!(
+                log.both_log_and_stderr(),
+                "{red}{} is {:.1}% faster than {}, more than --fail-if-faster {}%; \
+                    check the benchmark still does its work{reset}",
+                c.other.colored(summary.label(c.other)),
+                (1.0 - c.ratio) * 100.0,
+                c.base.colored(summary.label(c.base)),
+                percent,
+                red = palette.bad(),
+                reset = ansi::RESET,
+            )?;
+        }
+    }
+    Ok(pass)
+}
+
+/// Print the result of each comparison with the reference for each limit,
+/// and return true if no limit is exceeded.
+fn check_fail_if_worse(
+    log: &mut RunLog,
+    limits: &[WorseLimit],
+    palette: Palette,
+    measures: &AllMeasures,
+    summary: &SummaryReport,
+) -> anyhow::Result<bool> {
+    let mut all_pass = true;
+    for limit in limits {
+        // Checked when starting.
+        let measure = measures.get(limit.key).unwrap();
+        writeln!(
+            log.both_log_and_stderr(),
+            "Fail if worse: {} by more than {}%",
+            measure.name(),
+            limit.percent,
+        )?;
+        let measure_summary = match summary.measure(limit.key) {
+            Some(s) if !s.comparisons.is_empty() => s,
+            _ => {
+                writeln!(log.both_log_and_stderr(), "n/a: no comparisons")?;
+                continue;
+            }
+        };
+        for c in &measure_summary.comparisons {
+            let pass = !(c.is_worse(measure_summary.direction)
+                && limit.exceeded(c.ratio, measure_summary.direction));
+            all_pass &= pass;
+            writeln!(
+                log.both_log_and_stderr(),
+                "{}/{}: {:.3}x [{:.3}, {:.3}] {color}{}{reset}",
+                c.other.colored(summary.label(c.other)),
+                c.base.colored(summary.label(c.base)),
+                c.ratio,
+                c.ratio_min,
+                c.ratio_max,
+                if pass { "pass" } else { "fail" },
+                color = if pass { palette.good() } else { palette.bad() },
+                reset = ansi::RESET,
+            )?;
+        }
+    }
+    Ok(all_pass)
+}
+
+/// Print how much the verdict of each measure can be trusted.
+fn write_confidence(
+    log: &mut RunLog,
+    config: &RunConfig,
+    measures: &AllMeasures,
+    experiments: &ExperimentMap<Experiment>,
+) -> anyhow::Result<()> {
+    let trimmed = without_outliers(config, experiments);
+    let experiments = trimmed.as_ref().unwrap_or(experiments);
+    let options = config.render_options();
+    let summary = SummaryReport::new(measures, experiments, config.reference);
+    let mut first = true;
+    for measure in &summary.measures {
+        if let Some(confidence) = measure.confidence(
+            options.alpha,
+            options.cv_warn,
+            options.cv_bad,
+            &summary.labels,
+        ) {
+            if first {
+                writeln!(log.both_log_and_stderr())?;
+                first = false;
+            }
+            writeln!(
+                log.both_log_and_stderr(),
+                "{}: {}",
+                measure.name,
+                confidence
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Print experiments ranked by [`AllMeasures::composite_scores`].
+fn write_score(
+    log: &mut RunLog,
+    config: &RunConfig,
+    weights: &ScoreWeights,
+    measures: &AllMeasures,
+    experiments: &ExperimentMap<Experiment>,
+) -> anyhow::Result<()> {
+    let trimmed = without_outliers(config, experiments);
+    let experiments = trimmed.as_ref().unwrap_or(experiments);
+    let summary = SummaryReport::new(measures, experiments, config.reference);
+    let scores = measures.composite_scores(&summary, weights);
+    if scores.is_empty() {
+        return Ok(());
+    }
+    writeln!(log.both_log_and_stderr())?;
+    writeln!(
+        log.both_log_and_stderr(),
+        "Score ({}, relative to {}, lower is better):",
+        weights,
+        experiments[config.reference].label
+    )?;
+    for (i, (name, score)) in scores.iter().enumerate() {
+        writeln!(
+            log.both_log_and_stderr(),
+            "{}. {}: {:.3}",
+            i + 1,
+            experiments[*name].name_colored(),
+            score
+        )?;
+    }
+    Ok(())
+}
+
+/// Warn about wall time samples which make the mean a poor summary.
+fn write_distribution_warnings(
+    log: &mut RunLog,
+    experiments: &ExperimentMap<Experiment>,
+) -> anyhow::Result<()> {
+    for test in experiments.values() {
+        if early_samples_greater(&test.measures[MeasureKey::WallTime]) {
+            writeln!(
+                log.at(Verbosity::Quiet),
+                "{yellow}{}'s early runs are significantly slower; consider more warmup or -i.{reset}",
+                test.name_colored(),
+                yellow = ansi::YELLOW,
+                reset = ansi::RESET,
+            )?;
+        }
+        if appears_bimodal(&test.measures[MeasureKey::WallTime]) {
+            writeln!(
+                log.at(Verbosity::Quiet),
+                "{yellow}{} appears bimodal; the mean may be misleading, see the distribution plot.{reset}",
+                test.name_colored(),
+                yellow = ansi::YELLOW,
+                reset = ansi::RESET,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Render final stats and report why the session ended.
+///
+/// Return true if all targets, `fail_if_faster` and `fail_if_worse` checks pass.
+fn finish(
+    log: &mut RunLog,
+    config: &RunConfig,
+    measures: &AllMeasures,
+    experiments: &ExperimentMap<Experiment>,
+    stop_reason: &StopReason,
+) -> anyhow::Result<bool> {
+    let min_count = experiments.values().map(|t| t.runs()).min().unwrap();
+    if config.raw_only {
+        write_raw(log, config, measures, experiments)?;
+    } else if min_count >= 2 {
+        // Deltas are only of interest while running.
+        let mut previous_means = MeasureMap::new_all_default();
+        render_stats(
+            log,
+            config,
+            measures,
+            experiments,
+            &mut previous_means,
+            Verbosity::Quiet,
+        )?;
+        write_distribution_warnings(log, experiments)?;
+        write_confidence(log, config, measures, experiments)?;
+        if let Some(weights) = &config.score {
+            write_score(log, config, weights, measures, experiments)?;
+        }
+    }
+    writeln!(log.both_log_and_stderr())?;
+    writeln!(log.both_log_and_stderr(), "Stopped: {}.", stop_reason)?;
+    log.event(&Event::Stop {
+        reason: &stop_reason.to_string(),
+    })?;
+    if let Some(iterations) = config.iterations {
+        for test in experiments.values() {
+            if test.runs() < iterations as usize && auto_precision(config, test).is_none() {
+                writeln!(
+                    log.both_log_and_stderr(),
+                    "{yellow}{} has {} of {} requested successful runs ({} failed attempts).{reset}",
+                    test.name_colored(),
+                    test.runs(),
+                    iterations,
+                    test.attempts - test.successes,
+                    yellow = ansi::YELLOW,
+                    reset = ansi::RESET,
+                )?;
+            }
+        }
+    }
+    for test in experiments.values() {
+        if test.warmup.is_empty() {
+            continue;
+        }
+        // Whether a slow warmup, e.g. a cold cache, leaks into the measurement.
+        if let Some(r) = pearson(
+            &test.warmup_times,
+            test.measures[MeasureKey::WallTime].raw(),
+        ) {
+            writeln!(
+                log.both_log_and_stderr(),
+                "{}: warmup time vs wall time correlation: {:.2}",
+                test.name_colored(),
+                r,
+            )?;
+        }
+    }
+    for test in experiments.values() {
+        if test.clock_anomalies != 0 {
+            writeln!(
+                log.both_log_and_stderr(),
+                "{yellow}{}: {} samples discarded due to clock anomalies.{reset}",
+                test.name_colored(),
+                test.clock_anomalies,
+                yellow = ansi::YELLOW,
+                reset = ansi::RESET,
+            )?;
+        }
+        if test.sleep_discards != 0 {
+            writeln!(
+                log.both_log_and_stderr(),
+                "{yellow}{}: {} samples discarded because the machine slept.{reset}",
+                test.name_colored(),
+                test.sleep_discards,
+                yellow = ansi::YELLOW,
+                reset = ansi::RESET,
+            )?;
+        }
+    }
+    // Reports and checks use the same samples as the printed stats.
+    let trimmed = without_outliers(config, experiments);
+    let experiments = trimmed.as_ref().unwrap_or(experiments);
+    let summary = SummaryReport::new(measures, experiments, config.reference);
+    if config.tap {
+        write_or_exit(&mut io::stdout(), &tap_report(&summary)?)?;
+    }
+    if let Some((other, base)) = config.oneline {
+        let line = oneline(&summary, other, base);
+        write_or_exit(&mut io::stdout(), &format!("{}\n", line))?;
+    }
+    let mut pass = check_targets(log, &config.targets, config.palette, measures, experiments)?;
+    if let Some(percent) = config.fail_if_faster {
+        pass &= check_fail_if_faster(log, percent, config.palette, &summary)?;
+    }
+    pass &= check_fail_if_worse(
+        log,
+        &config.fail_if_worse,
+        config.palette,
+        measures,
+        &summary,
+    )?;
+    Ok(pass)
+}
+
+/// Samples collected by [`run`].
+pub struct BenchmarkResults {
+    pub experiments: ExperimentMap<Experiment>,
+    pub measures: AllMeasures,
+    pub stop_reason: StopReason,
+    /// Base of comparisons, see [`RunConfig::reference`].
+    pub reference: ExperimentName,
+    /// All of [`RunConfig::targets`] are met, no experiment is suspiciously
+    /// faster, see [`RunConfig::fail_if_faster`], and none regressed beyond
+    /// [`RunConfig::fail_if_worse`].
+    pub checks_passed: bool,
+}
+
+impl BenchmarkResults {
+    /// Stats for the measure, if all experiments recording it have enough samples.
+    pub fn stats(&self, key: MeasureKey) -> Option<ExperimentMap<Stats<u64>>> {
+        let experiments = recording(&self.experiments, key);
+        if experiments.count() == 0 || experiments.values().any(|t| t.measures[key].len() < 2) {
+            return None;
+        }
+        Some(experiments.map(|t| t.measures[key].stats().unwrap()))
+    }
+
+    /// Stats and comparisons for all measures with enough samples.
+    pub fn summary(&self) -> SummaryReport {
+        SummaryReport::new(&self.measures, &self.experiments, self.reference)
+    }
+}
+
+/// Run the benchmark until iterations or duration limit is reached, or a signal is received.
+pub fn run(mut config: RunConfig) -> anyhow::Result<BenchmarkResults> {
+    if (config.mem || !config.mem_experiments.is_empty()) && !max_rss_available()? {
+        let message = "max RSS is not reported for child processes on this platform \
+            (this is common in containers and on some operating systems)";
+        if config.strict {
+            return Err(anyhow::anyhow!("{}", message));
+        }
+        writeln!(
+            ConsoleWriter::new(config.color),
+            "{yellow}{}; not measuring max RSS{reset}",
+            message,
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+        config.mem = false;
+        config.mem_experiments.clear();
+    }
+
+    if config.avg_rss && !rss_sampling_available() {
+        return Err(anyhow::anyhow!(
+            "RSS sampling is only supported on Linux with /proc"
+        ));
+    }
+
+    if let Some(iterations) = config.iterations {
+        if config.min_iterations > iterations {
+            return Err(anyhow::anyhow!(
+                "--min-iters {} is more than --max-iters {}",
+                config.min_iterations,
+                iterations
+            ));
+        }
+    }
+
+    if config.metric_file.is_some() && config.metric_scope != MetricScope::Run {
+        return Err(anyhow::anyhow!(
+            "metric file is read after each run, metric scope must be run"
+        ));
+    }
+
+    let recorded = |key: MeasureKey| match key {
+        MeasureKey::WallTime => true,
+        MeasureKey::MaxRss => config.mem || !config.mem_experiments.is_empty(),
+        MeasureKey::UserDefinedMetric => config.records_metric(),
+        MeasureKey::Throughput => config.work_units.is_some(),
+    };
+    for target in &config.targets {
+        if !recorded(target.key) {
+            return Err(anyhow::anyhow!(
+                "target measure is not recorded (use -m for rss and --metric for metric)"
+            ));
+        }
+    }
+    for limit in &config.fail_if_worse {
+        if !recorded(limit.key) {
+            return Err(anyhow::anyhow!(
+                "--fail-if-worse measure is not recorded (use -m for rss and --metric for metric)"
+            ));
+        }
+        if limit.key == MeasureKey::UserDefinedMetric
+            && config.metric_direction == Direction::Neutral
+        {
+            return Err(anyhow::anyhow!(
+                "--fail-if-worse metric requires --metric-direction"
+            ));
+        }
+    }
+    for &(key, _) in config.score.iter().flat_map(|s| &s.0) {
+        if !recorded(key) {
+            return Err(anyhow::anyhow!(
+                "--score measure {} is not recorded",
+                key.id()
+            ));
+        }
+        if key == MeasureKey::UserDefinedMetric && config.metric_direction == Direction::Neutral {
+            return Err(anyhow::anyhow!(
+                "--score metric requires --metric-direction"
+            ));
+        }
+    }
+
+    if let Some(stdin_file) = &config.stdin_file {
+        File::open(stdin_file)
+            .map_err(|e| anyhow::anyhow!("failed to open {}: {}", stdin_file.display(), e))?;
+    }
+
+    let mut experiments = ExperimentMap::default();
+    experiments.insert(
+        ExperimentName::A,
+        Experiment::new(
+            ExperimentName::A,
+            config.aw.clone().unwrap_or_default(),
+            config.a.clone(),
+        ),
+    );
+
+    fn parse_opt_test(
+        tests: &mut ExperimentMap<Experiment>,
+        name: ExperimentName,
+        run: &Option<String>,
+        warmup: &Option<String>,
+    ) {
+        if let Some(run) = run.clone() {
+            tests.insert(
+                name,
+                Experiment::new(name, warmup.clone().unwrap_or_default(), run),
+            );
+        }
+    }
+    parse_opt_test(&mut experiments, ExperimentName::B, &config.b, &config.bw);
+    parse_opt_test(&mut experiments, ExperimentName::C, &config.c, &config.cw);
+    parse_opt_test(&mut experiments, ExperimentName::D, &config.d, &config.dw);
+    parse_opt_test(&mut experiments, ExperimentName::E, &config.e, &config.ew);
+
+    let labels = [
+        &config.name_a,
+        &config.name_b,
+        &config.name_c,
+        &config.name_d,
+        &config.name_e,
+    ];
+    for (name, test) in experiments.iter_mut() {
+        if let Some(label) = labels[name.index()] {
+            test.label = label.clone();
+        }
+    }
+
+    if let Some((other, base)) = config.oneline {
+        for name in [other, base] {
+            if experiments.get(name).is_none() {
+                return Err(anyhow::anyhow!(
+                    "--oneline compares {} with {}, but {} is not defined",
+                    other.name(),
+                    base.name(),
+                    name.name()
+                ));
+            }
+        }
+    }
+
+    if experiments.get(config.reference).is_none() {
+        return Err(anyhow::anyhow!(
+            "reference experiment {} is not defined",
+            config.reference.name()
+        ));
+    }
+
+    if let Some(capacity) = config.max_samples_stored {
+        for test in experiments.values_mut() {
+            test.set_streaming(capacity);
+        }
+    }
+
+    if !config.mem && !config.mem_experiments.is_empty() {
+        for test in experiments.values_mut() {
+            test.skipped[MeasureKey::MaxRss] = !config.mem_experiments.contains(&test.name);
+        }
+    }
+
+    // Checked before the log is opened, not to leave a log of a session which never ran.
+    let mut identical_warnings = Vec::new();
+    let names: Vec<ExperimentName> = experiments.keys().collect();
+    for (i, &a) in names.iter().enumerate() {
+        for &b in &names[i + 1..] {
+            if experiments[a].run != experiments[b].run {
+                continue;
+            }
+            let message = format!(
+                "experiments {} and {} have identical scripts, comparing a script to itself is likely a mistake",
+                experiments[a].label, experiments[b].label
+            );
+            if config.strict {
+                return Err(anyhow::anyhow!("{}", message));
+            }
+            identical_warnings.push(message);
+        }
+    }
+
+    let mut log = RunLog::open(config.color);
+    if config.log_format == LogFormat::Json {
+        log.enable_events()?;
+    }
+
+    shutdown::install();
+
+    for message in identical_warnings {
+        writeln!(
+            log.stderr_only(),
+            "{yellow}{}{reset}",
+            message,
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+    }
+
+    log.set_verbosity(config.verbosity);
+    if log.shows(Verbosity::Normal) {
+        let mut message = format!("Writing absh data to {}/\n", log.name().display());
+        if let Some(last) = log.last() {
+            writeln!(message, "Log symlink is {}", last.display())?;
+        }
+        write_or_exit(&mut io::stderr(), &message)?;
+    }
+
+    log.write_args()?;
+    log.write_env_snapshot(&EnvSnapshot::capture())?;
+
+    writeln!(log.log_only(), "version: {}", crate::VERSION)?;
+    writeln!(log.log_only(), "random_order: {}", config.random_order)?;
+    writeln!(log.log_only(), "verbosity: {}", config.verbosity.name())?;
+    if config.isolate {
+        writeln!(log.log_only(), "isolate: true")?;
+    }
+    for (key, value) in &config.env {
+        writeln!(log.log_only(), "env: {}={}", key, value)?;
+    }
+    if config.time_offset != Duration::default() {
+        writeln!(log.log_only(), "time_offset: {}", config.time_offset)?;
+    }
+    if config.metric_offset != 0 {
+        writeln!(log.log_only(), "metric_offset: {}", config.metric_offset)?;
+    }
+    if let Some(metric) = &config.metric {
+        writeln!(log.log_only(), "metric: {}", metric)?;
+    }
+    if let Some(metric_file) = &config.metric_file {
+        writeln!(log.log_only(), "metric_file: {}", metric_file.display())?;
+    }
+    let cwd = env::current_dir()?;
+    for (n, t) in experiments.iter_mut() {
+        writeln!(log.log_only(), "{}.run: {}", n.name(), t.run)?;
+        if !t.warmup.is_empty() {
+            writeln!(log.log_only(), "{}.warmup: {}", n.name(), t.warmup)?;
+        }
+        if t.label != n.name() {
+            writeln!(log.log_only(), "{}.label: {}", n.name(), t.label)?;
+        }
+        if config.inner_runs[n.index()] != 1 {
+            writeln!(
+                log.log_only(),
+                "{}.inner_runs: {}",
+                n.name(),
+                config.inner_runs[n.index()]
+            )?;
+        }
+        // Without `ABSH_TMPDIR`, which is a new directory for each run.
+        writeln!(log.log_only(), "{}.command:", n.name())?;
+        writeln!(log.log_only(), "    cwd: {}", cwd.display())?;
+        if !t.warmup.is_empty() {
+            writeln!(
+                log.log_only(),
+                "    warmup: {}",
+                command_line(&t.warmup, &config.warmup_spawn_options())
+            )?;
+        }
+        writeln!(
+            log.log_only(),
+            "    run: {}",
+            command_line(&t.run, &config.run_spawn_options())
+        )?;
+    }
+
+    if let Some(between) = &config.between {
+        writeln!(log.log_only(), "between: {}", between)?;
+    }
+    if let Some(global_warmup) = &config.global_warmup {
+        writeln!(log.log_only(), "global_warmup: {}", global_warmup)?;
+        writeln!(log.at(Verbosity::Normal))?;
+        writeln!(log.at(Verbosity::Normal), "running global warmup script:")?;
+        for line in global_warmup.lines() {
+            writeln!(log.at(Verbosity::Normal), "    {}", line)?;
+        }
+        let status = spawn_sh(global_warmup, &config.spawn_options(false))?.wait()?;
+        if !status.success() {
+            return Err(ScriptFailed(format!("global warmup failed: {}", status)).into());
+        }
+    }
+
+    if config.preflight {
+        for test in experiments.values() {
+            let mut scripts = vec![
+                ("warmup", &test.warmup, config.warmup_spawn_options()),
+                ("run", &test.run, config.run_spawn_options()),
+            ];
+            if let Some(load) = &config.load {
+                scripts.push(("load", load, config.spawn_options(true)));
+            }
+            for (kind, script, options) in scripts {
+                if script.is_empty() {
+                    continue;
+                }
+                if let Some(problem) = preflight(script, &options)? {
+                    return Err(ScriptFailed(format!(
+                        "{} {} script: {} (use --no-preflight to skip this check)",
+                        test.label, kind, problem
+                    ))
+                    .into());
+                }
+            }
+        }
+    }
+
+    if config.warmup_only {
+        run_warmups_only(&mut log, &config, &experiments)?;
+        return Ok(BenchmarkResults {
+            experiments,
+            measures: AllMeasures(Vec::new()),
+            stop_reason: StopReason::WarmupOnly,
+            reference: config.reference,
+            checks_passed: true,
+        });
+    }
+
+    let mut order = PairOrder::new(
+        config.random_order,
+        config.replay_order.as_deref(),
+        config.order.as_deref(),
+        &experiments.keys().collect::<Vec<_>>(),
+    )?;
+    if let Some(replay_order) = &config.replay_order {
+        writeln!(log.log_only(), "replay_order: {}", replay_order.display())?;
+    }
+    if let Some(fixed) = &config.order {
+        writeln!(log.log_only(), "order: {}", format_order(fixed))?;
+    }
+
+    // Pair scope metric is a counter, so the first pair needs a reading to start from.
+    let mut last_metric = match &config.metric {
+        Some(metric) if config.metric_scope == MetricScope::Pair => run_metric(&mut log, metric)?,
+        _ => None,
+    };
+
+    let ignore_first = config.ignore_first;
+    if ignore_first != 0 {
+        for _ in 0..ignore_first {
+            run_pair(
+                &mut log,
+                &config,
+                &mut order,
+                &mut last_metric,
+                &mut experiments,
+            )?;
+        }
+
+        for test in experiments.values_mut() {
+            test.clear();
+        }
+
+        writeln!(log.at(Verbosity::Normal))?;
+        if ignore_first == 1 {
+            writeln!(
+                log.at(Verbosity::Normal),
+                "Ignoring first run pair results."
+            )?;
+        } else {
+            writeln!(
+                log.at(Verbosity::Normal),
+                "Ignoring results of the first {} run pairs.",
+                ignore_first
+            )?;
+        }
+        writeln!(log.at(Verbosity::Normal), "Now collecting the results.")?;
+        writeln!(
+            log.at(Verbosity::Normal),
+            "Statistics will be printed after the second successful iteration."
+        )?;
+    } else {
+        writeln!(log.at(Verbosity::Normal))?;
+        writeln!(
+            log.at(Verbosity::Normal),
+            "{yellow}First run pair results will be used in statistics.{reset}",
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+        writeln!(
+            log.at(Verbosity::Normal),
+            "{yellow}Results might be skewed.{reset}",
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+        writeln!(
+            log.at(Verbosity::Normal),
+            "{yellow}Use `-i` command line flag to ignore the first iteration.{reset}",
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+    }
+
+    let mut measures: Vec<Box<dyn MeasureDyn>> = Vec::new();
+    measures.push(Box::new(WallTime::new(config.time_unit, config.sigfigs)));
+    if config.work_units.is_some() {
+        measures.push(Box::new(Throughput::new(!config.no_thousands)));
+    }
+    if config.mem || !config.mem_experiments.is_empty() {
+        measures.push(Box::new(MaxRss::new(!config.no_thousands)));
+    }
+    if config.records_metric() {
+        measures.push(Box::new(UserDefinedMetric::new(
+            config.metric_name.as_deref(),
+            config.metric_unit.as_deref(),
+            config.metric_direction,
+            !config.no_thousands,
+        )));
+    }
+    let measures = AllMeasures(measures);
+
+    let start = Instant::now();
+    let mut previous_means = MeasureMap::new_all_default();
+
+    // Stopping rule, checked between pairs in this order:
+    // a signal always stops; `iterations` stops once every experiment has that many
+    // successful runs; `max_duration` and `auto` only stop once every experiment
+    // has `min_iterations`.
+    let stop_reason = loop {
+        if let Some(signal) = shutdown::requested() {
+            break StopReason::Signal(signal);
+        }
+        let min_reached = experiments
+            .values()
+            .all(|t| t.runs() >= config.min_iterations as usize);
+        if let (true, Some(max_duration)) = (min_reached, config.max_duration) {
+            if Duration::from_std(start.elapsed()) >= max_duration {
+                break StopReason::MaxDuration(max_duration);
+            }
+        }
+
+        run_pair(
+            &mut log,
+            &config,
+            &mut order,
+            &mut last_metric,
+            &mut experiments,
+        )?;
+
+        let min_count = experiments.values_mut().map(|t| t.runs()).min().unwrap();
+        // Experiments converged with `auto` stop short of `-n`.
+        let unfinished = experiments
+            .values()
+            .filter(|t| auto_precision(&config, t).is_none())
+            .map(|t| t.runs())
+            .min();
+        match (unfinished, config.iterations) {
+            (None, _) => break StopReason::Converged(config.auto.unwrap()),
+            (Some(runs), Some(iterations)) if runs >= iterations as usize => {
+                if !config.prompt
+                    || !atty::is(atty::Stream::Stdin)
+                    || !atty::is(atty::Stream::Stderr)
+                {
+                    break StopReason::Iterations(iterations);
+                }
+                if !config.raw_only && min_count >= 2 {
+                    render_stats(
+                        &mut log,
+                        &config,
+                        &measures,
+                        &experiments,
+                        &mut previous_means,
+                        Verbosity::Normal,
+                    )?;
+                }
+                match prompt_more_iterations()? {
+                    Some(more) => {
+                        writeln!(log.log_only(), "continuing for {} more iterations", more)?;
+                        config.iterations = Some(iterations.saturating_add(more));
+                        continue;
+                    }
+                    None => break StopReason::Iterations(iterations),
+                }
+            }
+            _ => {}
+        }
+
+        if config.raw_only || min_count < 2 || min_count % config.report_every as usize != 0 {
+            continue;
+        }
+
+        render_stats(
+            &mut log,
+            &config,
+            &measures,
+            &experiments,
+            &mut previous_means,
+            Verbosity::Normal,
+        )?;
+    };
+
+    let checks_passed = finish(&mut log, &config, &measures, &experiments, &stop_reason)?;
+    Ok(BenchmarkResults {
+        experiments,
+        measures,
+        stop_reason,
+        reference: config.reference,
+        checks_passed,
+    })
+}
@@ -0,0 +1,50 @@
+//! Long running process benchmarked by a separate load script.
+
+use std::process::Child;
+use std::process::ExitStatus;
+
+use crate::duration::Duration;
+use crate::sh::SpawnOptions;
+use crate::sh::spawn_sh;
+use crate::timeout::Signal;
+use crate::timeout::Timeout;
+use crate::timeout::Watchdog;
+
+/// Process group started by a run script, terminated when dropped.
+pub struct Server {
+    child: Child,
+    /// Time to wait after `SIGTERM` before sending `SIGKILL`.
+    grace: Duration,
+}
+
+impl Server {
+    /// Start the script in the background, `options` must request a process group.
+    pub fn start(script: &str, options: &SpawnOptions, grace: Duration) -> anyhow::Result<Server> {
+        assert!(options.process_group);
+        Ok(Server {
+            child: spawn_sh(script, options)?,
+            grace,
+        })
+    }
+
+    /// Exit status if the server has already exited.
+    pub fn exited(&mut self) -> anyhow::Result<Option<ExitStatus>> {
+        Ok(self.child.try_wait()?)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        // Signal the whole group immediately, and kill it if it does not exit in time.
+        let watchdog = Watchdog::start(
+            &self.child,
+            &Timeout {
+                duration: Duration::from_nanos(0),
+                signal: Signal::TERM,
+                grace: self.grace,
+            },
+        );
+        let _ = self.child.wait();
+        watchdog.finish();
+    }
+}
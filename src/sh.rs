@@ -1,19 +1,272 @@
+use std::fs::File;
+use std::path::PathBuf;
 use std::process::Child;
 use std::process::Command;
 use std::process::Stdio;
 
+use crate::shell::shell_quote;
+
+#[cfg(not(windows))]
+fn sh_command(script: &str) -> Command {
+    let mut command = Command::new("/bin/sh");
+    command.args(["-ec", script]);
+    command
+}
+
+#[cfg(windows)]
+fn sh_command(script: &str) -> Command {
+    let mut command = Command::new("powershell.exe");
+    command.args(["-Command", script]);
+    command
+}
+
+/// Environment variables kept when the environment is isolated.
+const ISOLATED_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "TERM",
+    "TMPDIR",
+    "LANG",
+    // Required to run anything on Windows.
+    "SYSTEMROOT",
+    "TEMP",
+    "TMP",
+];
+
+/// How to spawn a script.
+#[derive(Default)]
+pub struct SpawnOptions {
+    /// Run the script in a new process group (so the whole group can be killed).
+    pub process_group: bool,
+    /// Do not inherit the environment except for a few essential variables.
+    pub isolate: bool,
+    /// Additional environment variables.
+    pub env: Vec<(String, String)>,
+    /// File opened as stdin, null if not specified.
+    pub stdin: Option<PathBuf>,
+    /// Pipe stdout and stderr instead of inheriting them, see `Capture`.
+    pub capture: bool,
+}
+
+impl SpawnOptions {
+    /// Copy with an additional environment variable.
+    pub fn with_env(&self, name: &str, value: &str) -> SpawnOptions {
+        let mut env = self.env.clone();
+        env.push((name.to_owned(), value.to_owned()));
+        SpawnOptions {
+            env,
+            stdin: self.stdin.clone(),
+            ..*self
+        }
+    }
+}
+
+pub fn spawn_sh(script: &str, options: &SpawnOptions) -> anyhow::Result<Child> {
+    let mut command = sh_command(script);
+    match &options.stdin {
+        Some(path) => command.stdin(
+            File::open(path)
+                .map_err(|e| anyhow::anyhow!("failed to open {}: {}", path.display(), e))?,
+        ),
+        None => command.stdin(Stdio::null()),
+    };
+    if options.capture {
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+    }
+    if options.isolate {
+        command.env_clear();
+        for (name, value) in std::env::vars_os() {
+            if ISOLATED_ENV.iter().any(|n| name.eq_ignore_ascii_case(n)) {
+                command.env(name, value);
+            }
+        }
+    }
+    command.envs(options.env.iter().map(|(k, v)| (k, v)));
+    #[cfg(unix)]
+    if options.process_group {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    if options.process_group {
+        return Err(anyhow::anyhow!(
+            "process groups are not supported on this platform"
+        ));
+    }
+    Ok(command.spawn()?)
+}
+
+/// Script running `script` `n` times in a row, stopping at the first failure.
+#[cfg(not(windows))]
+pub fn repeat_script(script: &str, n: u32) -> String {
+    format!(
+        "absh_inner=0\nwhile [ $absh_inner -lt {} ]; do\n(\n{}\n)\nabsh_inner=$((absh_inner + 1))\ndone",
+        n, script
+    )
+}
+
+#[cfg(windows)]
+pub fn repeat_script(script: &str, n: u32) -> String {
+    format!("for ($i = 0; $i -lt {}; $i++) {{\n{}\n}}", n, script)
+}
+
+/// Shell command line equivalent to `spawn_sh`, to reproduce a run elsewhere.
+pub fn command_line(script: &str, options: &SpawnOptions) -> String {
+    let command = sh_command(script);
+    let mut words = Vec::new();
+    if options.isolate || !options.env.is_empty() {
+        words.push("env".to_owned());
+    }
+    if options.isolate {
+        words.push("-i".to_owned());
+        for (name, value) in std::env::vars() {
+            if ISOLATED_ENV.iter().any(|n| name.eq_ignore_ascii_case(n)) {
+                words.push(shell_quote(&format!("{}={}", name, value)));
+            }
+        }
+    }
+    for (name, value) in &options.env {
+        words.push(shell_quote(&format!("{}={}", name, value)));
+    }
+    words.push(shell_quote(&command.get_program().to_string_lossy()));
+    for arg in command.get_args() {
+        words.push(shell_quote(&arg.to_string_lossy()));
+    }
+    words.push("<".to_owned());
+    words.push(match &options.stdin {
+        Some(path) => shell_quote(&path.to_string_lossy()),
+        None => "/dev/null".to_owned(),
+    });
+    words.join(" ")
+}
+
+/// First word of the script if it is a command name to look up,
+/// e.g. `make` of `make -j8 && make test`.
+///
+/// `None` for assignments, expansions, quoting and function definitions.
 #[cfg(not(windows))]
-pub fn spawn_sh(script: &str) -> anyhow::Result<Child> {
-    Ok(Command::new("/bin/sh")
-        .args(&["-ec", &script])
+fn first_command(script: &str) -> Option<&str> {
+    let line = script
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with('#'))?;
+    let end = line
+        .find(|c: char| c.is_whitespace() || ";&|<>()".contains(c))
+        .unwrap_or(line.len());
+    let (word, rest) = line.split_at(end);
+    if word.is_empty()
+        || word.contains(|c: char| "=$`'\"\\*?[{~".contains(c))
+        || rest.trim_start().starts_with('(')
+    {
+        return None;
+    }
+    Some(word)
+}
+
+/// Check without running the script that it parses and its first command exists.
+///
+/// Returns the shell's complaint, e.g. `command not found: mkae`.
+#[cfg(not(windows))]
+pub fn preflight(script: &str, options: &SpawnOptions) -> anyhow::Result<Option<String>> {
+    let output = Command::new("/bin/sh")
+        .args(["-nc", script])
         .stdin(Stdio::null())
-        .spawn()?)
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Ok(Some(format!("syntax error: {}", stderr.trim())));
+    }
+    let command = match first_command(script) {
+        Some(command) => command,
+        None => return Ok(None),
+    };
+    let check = SpawnOptions {
+        process_group: false,
+        isolate: options.isolate,
+        env: options.env.clone(),
+        stdin: None,
+        capture: true,
+    };
+    let lookup = format!("command -v {}", shell_quote(command));
+    if !spawn_sh(&lookup, &check)?
+        .wait_with_output()?
+        .status
+        .success()
+    {
+        return Ok(Some(format!("command not found: {}", command)));
+    }
+    Ok(None)
 }
 
 #[cfg(windows)]
-pub fn spawn_sh(script: &str) -> anyhow::Result<Child> {
-    Ok(Command::new("powershell.exe")
-        .args(&["-Command", &script])
+pub fn preflight(_script: &str, _options: &SpawnOptions) -> anyhow::Result<Option<String>> {
+    Ok(None)
+}
+
+/// Run the script and return its stdout.
+pub fn sh_stdout(script: &str) -> anyhow::Result<String> {
+    sh_stdout_with_env(script, &[])
+}
+
+/// Run the script with additional environment variables and return its stdout.
+pub fn sh_stdout_with_env(script: &str, env: &[(&str, String)]) -> anyhow::Result<String> {
+    let output = sh_command(script)
+        .envs(env.iter().map(|(k, v)| (k, v)))
         .stdin(Stdio::null())
-        .spawn()?)
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("script failed: {}", output.status));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use crate::sh::SpawnOptions;
+    use crate::sh::command_line;
+    use crate::sh::first_command;
+    use crate::sh::preflight;
+
+    #[test]
+    fn command_line_with_env() {
+        let options = SpawnOptions::default().with_env("N", "1 2");
+        assert_eq!(
+            "env 'N=1 2' /bin/sh -ec 'sleep 1' < /dev/null",
+            command_line("sleep 1", &options)
+        );
+    }
+
+    #[test]
+    fn first_command_of_script() {
+        assert_eq!(
+            Some("make"),
+            first_command("# build\n\nmake -j8 && make test")
+        );
+        assert_eq!(Some("true"), first_command("true;false"));
+        assert_eq!(None, first_command("N=1 make"));
+        assert_eq!(None, first_command("\"$CC\" main.c"));
+        assert_eq!(None, first_command("f() { true; }; f"));
+        assert_eq!(None, first_command("f () { true; }; f"));
+    }
+
+    #[test]
+    fn preflight_scripts() {
+        let options = SpawnOptions::default();
+        assert_eq!(None, preflight("true && sleep 0", &options).unwrap());
+        assert_eq!(
+            Some("command not found: absh-no-such-command".to_owned()),
+            preflight("absh-no-such-command --flag", &options).unwrap()
+        );
+        assert!(
+            preflight("if true; then", &options)
+                .unwrap()
+                .unwrap()
+                .starts_with("syntax error: ")
+        );
+    }
 }
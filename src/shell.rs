@@ -15,9 +15,7 @@ pub fn shell_quote(s: &str) -> String {
     }
 
     // If a string does not need escaping, just return it.
-    if !s.contains(|c| {
-        c == '"' || c == '\'' || c == '\"' || c <= ' ' || c == '\\' || c == '$' || c == '`'
-    }) {
+    if !s.contains(|c| c == '"' || c == '\'' || c <= ' ' || c == '\\' || c == '$' || c == '`') {
         return s.to_owned();
     }
 
@@ -170,6 +168,6 @@ mod tests {
 
     #[test]
     fn test_shell_quote_args() {
-        assert_eq!("echo '$A'", shell_quote_args(&["echo", "$A"]));
+        assert_eq!("echo '$A'", shell_quote_args(["echo", "$A"]));
     }
 }
@@ -0,0 +1,39 @@
+//! Graceful shutdown on `SIGINT` and `SIGTERM`.
+//!
+//! Signal handler only records the signal, the main loop checks it
+//! between runs, and then writes final stats.
+
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::Ordering;
+
+use crate::timeout::Signal;
+
+static REQUESTED: AtomicI32 = AtomicI32::new(0);
+
+#[cfg(unix)]
+extern "C" fn handler(signal: libc::c_int) {
+    // Second signal means the user does not want to wait.
+    if REQUESTED.swap(signal, Ordering::SeqCst) != 0 {
+        unsafe {
+            libc::_exit(128 + signal);
+        }
+    }
+}
+
+/// Install handlers for `SIGINT` and `SIGTERM`.
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        let handler = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// Signal received, if any.
+pub fn requested() -> Option<Signal> {
+    match REQUESTED.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(Signal::from_raw(signal)),
+    }
+}
@@ -10,6 +10,7 @@ pub enum ConfInterval {
 pub const TWO_SIDED_95: ConfInterval = ConfInterval::C_97_5_95_0;
 
 /// Query precomputed t-table.
+#[allow(clippy::approx_constant)]
 pub fn t_table(v: u64, conf_interval: ConfInterval) -> f64 {
     assert!(v >= 1);
     // One-sided              75%    80%    85%    90%    95%    97.5%  99%    99.5%  99.75% 99.9%  99.95%
@@ -232,7 +233,7 @@ pub fn t_table(v: u64, conf_interval: ConfInterval) -> f64 {
             ],
         ),
         (
-            u64::max_value(),
+            u64::MAX,
             [
                 0.674, 0.842, 1.036, 1.282, 1.645, 1.960, 2.326, 2.576, 2.807, 3.090, 3.291,
             ],
@@ -249,3 +250,109 @@ pub fn t_table(v: u64, conf_interval: ConfInterval) -> f64 {
     }
     unreachable!()
 }
+
+/// Natural logarithm of the gamma function (Lanczos approximation).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000000000190015;
+    for (i, c) in COEFFICIENTS.iter().enumerate() {
+        series += c / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Continued fraction for the incomplete beta function.
+fn beta_cf(a: f64, b: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 200;
+    const EPS: f64 = 3e-14;
+    const TINY: f64 = 1e-300;
+
+    let qab = a + b;
+    let qap = a + 1.0;
+    let qam = a - 1.0;
+    let mut c = 1.0;
+    let mut d = 1.0 - qab * x / qap;
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+        let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        h *= d * c;
+        let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let del = d * c;
+        h *= del;
+        if (del - 1.0).abs() < EPS {
+            break;
+        }
+    }
+    h
+}
+
+/// Regularized incomplete beta function `I_x(a, b)`.
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_cf(a, b, x) / a
+    } else {
+        1.0 - front * beta_cf(b, a, 1.0 - x) / b
+    }
+}
+
+/// Two-sided p-value of Student's t statistic with `df` degrees of freedom.
+pub fn two_sided_p_value(t: f64, df: f64) -> f64 {
+    if !t.is_finite() {
+        return if t.is_nan() { 1.0 } else { 0.0 };
+    }
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::student::two_sided_p_value;
+
+    #[test]
+    fn p_value() {
+        assert!((two_sided_p_value(0.0, 10.0) - 1.0).abs() < 1e-9);
+        assert!((two_sided_p_value(2.228, 10.0) - 0.05).abs() < 1e-3);
+        assert!((two_sided_p_value(-2.228, 10.0) - 0.05).abs() < 1e-3);
+        assert!((two_sided_p_value(1.96, 1e6) - 0.05).abs() < 1e-3);
+        assert!(two_sided_p_value(50.0, 5.0) < 1e-6);
+    }
+}
@@ -0,0 +1,564 @@
+//! Computed statistics, shared by all renderers.
+
+use std::fmt;
+
+use crate::experiment::Experiment;
+use crate::experiment::recording;
+use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::math::stats::Stats;
+use crate::measure::direction::Direction;
+use crate::measure::key::MeasureKey;
+use crate::measure::tr::AllMeasures;
+
+/// Comparison of an experiment with the reference experiment, A by default.
+pub struct Comparison {
+    pub base: ExperimentName,
+    pub other: ExperimentName,
+    /// `other.mean / base.mean`.
+    pub ratio: f64,
+    /// 95% confidence interval of the ratio.
+    pub ratio_min: f64,
+    pub ratio_max: f64,
+    /// Probability of observing such difference if means are equal.
+    pub p_value: f64,
+}
+
+impl Comparison {
+    /// Whole confidence interval is on the better side.
+    pub fn is_better(&self, direction: Direction) -> bool {
+        direction.is_better(self.ratio_min) && direction.is_better(self.ratio_max)
+    }
+
+    /// Whole confidence interval is on the worse side.
+    pub fn is_worse(&self, direction: Direction) -> bool {
+        direction.is_worse(self.ratio_min) && direction.is_worse(self.ratio_max)
+    }
+}
+
+/// Best experiment of a measure.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Verdict {
+    Winner(ExperimentName),
+    /// Experiments not significantly different from the best, in name order.
+    Tied(Vec<ExperimentName>),
+}
+
+impl Verdict {
+    /// Like `B is the best`, using `labels` of experiments.
+    pub fn describe(&self, labels: &ExperimentMap<String>) -> String {
+        match self {
+            Verdict::Winner(name) => format!("{} is the best", labels[*name]),
+            Verdict::Tied(names) => {
+                let (last, rest) = names.split_last().unwrap();
+                let rest: Vec<&str> = rest.iter().map(|&n| labels[n].as_str()).collect();
+                format!(
+                    "{} and {} are statistically tied",
+                    rest.join(", "),
+                    labels[*last]
+                )
+            }
+        }
+    }
+}
+
+/// Samples of each experiment below which results are low confidence.
+pub const CONFIDENCE_MIN_SAMPLES: u64 = 30;
+
+/// How much a verdict can be trusted.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ConfidenceLevel {
+    High,
+    Medium,
+    Low,
+}
+
+/// Verdict qualified by sample count and noise, for readers who do not
+/// want to interpret confidence intervals.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Confidence {
+    pub level: ConfidenceLevel,
+    pub message: String,
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            ConfidenceLevel::High => "High",
+            ConfidenceLevel::Medium => "Medium",
+            ConfidenceLevel::Low => "Low",
+        };
+        write!(f, "{} confidence: {}", level, self.message)
+    }
+}
+
+pub struct MeasureSummary {
+    pub key: MeasureKey,
+    pub name: String,
+    pub direction: Direction,
+    pub stats: ExperimentMap<Stats<u64>>,
+    /// Base of the comparisons.
+    pub reference: ExperimentName,
+    /// Comparisons of each other experiment with the reference.
+    pub comparisons: Vec<Comparison>,
+}
+
+impl MeasureSummary {
+    pub fn new(
+        key: MeasureKey,
+        name: &str,
+        direction: Direction,
+        stats: ExperimentMap<Stats<u64>>,
+        reference: ExperimentName,
+    ) -> MeasureSummary {
+        // Reference may not record the measure, e.g. max RSS with `--mem-b`.
+        let base = match stats.get(reference) {
+            Some(_) => reference,
+            None => stats.keys().next().unwrap(),
+        };
+        let stats_base = &stats[base];
+        let comparisons = stats
+            .iter()
+            .filter(|&(other, _)| other != base)
+            .map(|(other, stats_other)| {
+                let (ratio_min, ratio_max) = stats_base.ratio_conf_interval(stats_other);
+                Comparison {
+                    base,
+                    other,
+                    ratio: stats_other.mean as f64 / stats_base.mean as f64,
+                    ratio_min,
+                    ratio_max,
+                    p_value: stats_base.welch_p_value(stats_other),
+                }
+            })
+            .collect();
+        MeasureSummary {
+            key,
+            name: name.to_owned(),
+            direction,
+            stats,
+            reference: base,
+            comparisons,
+        }
+    }
+}
+
+impl MeasureSummary {
+    /// Best experiment, or a tie if the difference from the best is not significant
+    /// at `alpha`. `None` if the measure has no preferred direction or one experiment.
+    pub fn verdict(&self, alpha: f64) -> Option<Verdict> {
+        if self.comparisons.is_empty() || self.direction == Direction::Neutral {
+            return None;
+        }
+        let better_first = |s: &Stats<u64>| match self.direction {
+            Direction::Higher => u64::MAX - s.mean,
+            _ => s.mean,
+        };
+        // Equal means are broken by name, so the result does not depend on run order.
+        let (best, best_stats) = self
+            .stats
+            .iter()
+            .min_by_key(|(name, s)| (better_first(s), name.index()))
+            .unwrap();
+        let tied: Vec<ExperimentName> = self
+            .stats
+            .iter()
+            .filter(|&(name, s)| name == best || best_stats.welch_p_value(s) >= alpha)
+            .map(|(name, _)| name)
+            .collect();
+        if tied.len() == 1 {
+            Some(Verdict::Winner(best))
+        } else {
+            Some(Verdict::Tied(tied))
+        }
+    }
+}
+
+impl MeasureSummary {
+    /// Coefficient of variation in percent of each experiment, how reproducible
+    /// its runs are. `None` if the mean is zero.
+    pub fn cv_percent(&self) -> ExperimentMap<Option<f64>> {
+        self.stats.map(|s| s.cv_percent())
+    }
+
+    /// Highest coefficient of variation of all experiments, zero if none has one.
+    pub fn max_cv_percent(&self) -> f64 {
+        self.cv_percent()
+            .values()
+            .flatten()
+            .fold(0.0, |a, &b| f64::max(a, b))
+    }
+
+    /// Combine the verdict at `alpha` with sample count and coefficient of variation:
+    /// CV at least `cv_bad` percent or too few samples is low confidence,
+    /// CV at least `cv_warn` percent or a tie is medium. `None` if there is no verdict.
+    pub fn confidence(
+        &self,
+        alpha: f64,
+        cv_warn: f64,
+        cv_bad: f64,
+        labels: &ExperimentMap<String>,
+    ) -> Option<Confidence> {
+        let verdict = self.verdict(alpha)?;
+        let confidence = |level, message: String| Some(Confidence { level, message });
+        let min_count = self.stats.values().map(|s| s.count).min().unwrap();
+        if min_count < CONFIDENCE_MIN_SAMPLES {
+            return confidence(
+                ConfidenceLevel::Low,
+                format!(
+                    "collect more samples ({} of at least {})",
+                    min_count, CONFIDENCE_MIN_SAMPLES
+                ),
+            );
+        }
+        let max_cv = self.max_cv_percent();
+        if max_cv >= cv_bad {
+            return confidence(
+                ConfidenceLevel::Low,
+                format!(
+                    "results are too noisy (CV {:.1}%), reduce noise or collect more samples",
+                    max_cv
+                ),
+            );
+        }
+        match verdict {
+            Verdict::Tied(_) => confidence(
+                ConfidenceLevel::Medium,
+                format!(
+                    "{}, a smaller difference may need more samples",
+                    verdict.describe(labels)
+                ),
+            ),
+            Verdict::Winner(_) if max_cv >= cv_warn => confidence(
+                ConfidenceLevel::Medium,
+                format!(
+                    "{}, but results are noisy (CV {:.1}%)",
+                    verdict.describe(labels),
+                    max_cv
+                ),
+            ),
+            Verdict::Winner(_) => confidence(ConfidenceLevel::High, verdict.describe(labels)),
+        }
+    }
+}
+
+/// Stats and comparisons for measures which have enough samples.
+pub struct SummaryReport {
+    pub measures: Vec<MeasureSummary>,
+    /// Label of each experiment, see `Experiment::label`.
+    pub labels: ExperimentMap<String>,
+}
+
+impl SummaryReport {
+    /// Compare experiments with `reference`, or the first experiment recording
+    /// the measure if the reference does not.
+    pub fn new(
+        measures: &AllMeasures,
+        tests: &ExperimentMap<Experiment>,
+        reference: ExperimentName,
+    ) -> SummaryReport {
+        SummaryReport {
+            measures: measures
+                .with_stats(tests)
+                .map(|m| {
+                    MeasureSummary::new(
+                        m.key(),
+                        m.name(),
+                        m.direction(),
+                        recording(tests, m.key()).map(|t| t.measures[m.key()].stats().unwrap()),
+                        reference,
+                    )
+                })
+                .collect(),
+            labels: tests.map(|t| t.label.clone()),
+        }
+    }
+
+    /// Copy with only the given measures, or all if `keys` is empty.
+    pub fn only(&self, keys: &[MeasureKey]) -> SummaryReport {
+        SummaryReport {
+            measures: self
+                .measures
+                .iter()
+                .filter(|m| keys.is_empty() || keys.contains(&m.key))
+                .map(|m| {
+                    MeasureSummary::new(
+                        m.key,
+                        &m.name,
+                        m.direction,
+                        m.stats.map(|s| s.clone()),
+                        m.reference,
+                    )
+                })
+                .collect(),
+            labels: self.labels.map(|l| l.clone()),
+        }
+    }
+
+    /// Label of the experiment, or the letter if it did not run.
+    pub fn label(&self, name: ExperimentName) -> &str {
+        match self.labels.get(name) {
+            Some(label) => label,
+            None => name.name(),
+        }
+    }
+
+    pub fn measure(&self, key: MeasureKey) -> Option<&MeasureSummary> {
+        self.measures.iter().find(|m| m.key == key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::math::stats::Stats;
+    use crate::measure::direction::Direction;
+    use crate::measure::key::MeasureKey;
+    use crate::measure::tr::AllMeasures;
+    use crate::measure::tr::MaxRss;
+    use crate::measure::tr::WallTime;
+    use crate::summary::ConfidenceLevel;
+    use crate::summary::MeasureSummary;
+    use crate::summary::SummaryReport;
+    use crate::summary::Verdict;
+
+    fn letters() -> ExperimentMap<String> {
+        let mut labels = ExperimentMap::default();
+        for i in 0..ExperimentName::COUNT {
+            let name = ExperimentName::from_index(i);
+            labels.insert(name, name.name().to_owned());
+        }
+        labels
+    }
+
+    fn measure_summary(direction: Direction, means_stds: &[(u64, u64)]) -> MeasureSummary {
+        let mut stats = ExperimentMap::default();
+        for (i, &(mean, std)) in means_stds.iter().enumerate() {
+            stats.insert(
+                ExperimentName::from_index(i),
+                Stats {
+                    count: 20,
+                    mean,
+                    med: mean,
+                    min: mean,
+                    max: mean,
+                    std,
+                    se: 0,
+                    sum: mean * 20,
+                },
+            );
+        }
+        MeasureSummary::new(
+            MeasureKey::WallTime,
+            "Time",
+            direction,
+            stats,
+            ExperimentName::A,
+        )
+    }
+
+    #[test]
+    fn verdict_winner() {
+        let summary = measure_summary(Direction::Lower, &[(1000, 10), (900, 10)]);
+        assert_eq!(
+            Some(Verdict::Winner(ExperimentName::B)),
+            summary.verdict(0.05)
+        );
+        let summary = measure_summary(Direction::Higher, &[(1000, 10), (900, 10)]);
+        assert_eq!(
+            Some(Verdict::Winner(ExperimentName::A)),
+            summary.verdict(0.05)
+        );
+    }
+
+    #[test]
+    fn verdict_near_equal_is_tie() {
+        // B is a bit faster, but well within noise.
+        let summary = measure_summary(Direction::Lower, &[(1000, 100), (999, 100), (500, 10)]);
+        assert_eq!(
+            Some(Verdict::Winner(ExperimentName::C)),
+            summary.verdict(0.05)
+        );
+        let summary = measure_summary(Direction::Lower, &[(1000, 100), (999, 100), (1500, 10)]);
+        let verdict = summary.verdict(0.05).unwrap();
+        assert_eq!(
+            Verdict::Tied(vec![ExperimentName::A, ExperimentName::B]),
+            verdict
+        );
+        assert_eq!(
+            "A and B are statistically tied",
+            verdict.describe(&letters())
+        );
+        let mut labels = letters();
+        labels.insert(ExperimentName::A, "main".to_owned());
+        assert_eq!(
+            "main and B are statistically tied",
+            verdict.describe(&labels)
+        );
+    }
+
+    #[test]
+    fn verdict_equal_means_deterministic() {
+        let summary = measure_summary(Direction::Lower, &[(1000, 0), (1000, 0), (1000, 0)]);
+        assert_eq!(
+            "A, B and C are statistically tied",
+            summary.verdict(0.05).unwrap().describe(&letters())
+        );
+        let summary = measure_summary(Direction::Lower, &[(1000, 5), (1000, 5)]);
+        assert_eq!(
+            Some(Verdict::Tied(vec![ExperimentName::A, ExperimentName::B])),
+            summary.verdict(0.05)
+        );
+    }
+
+    #[test]
+    fn verdict_none() {
+        let neutral = measure_summary(Direction::Neutral, &[(1000, 10), (900, 10)]);
+        assert_eq!(None, neutral.verdict(0.05));
+        let single = measure_summary(Direction::Lower, &[(1000, 10)]);
+        assert_eq!(None, single.verdict(0.05));
+    }
+
+    #[test]
+    fn confidence() {
+        let level = |summary: &MeasureSummary| {
+            summary
+                .confidence(0.05, 2.0, 10.0, &letters())
+                .unwrap()
+                .level
+        };
+        // 20 samples each.
+        let summary = measure_summary(Direction::Lower, &[(1000, 10), (900, 10)]);
+        assert_eq!(ConfidenceLevel::Low, level(&summary));
+
+        let with_count = |means_stds: &[(u64, u64)]| {
+            let mut summary = measure_summary(Direction::Lower, means_stds);
+            summary.stats = summary.stats.map(|s| Stats { count: 100, ..*s });
+            summary
+        };
+        let high = with_count(&[(1000, 10), (900, 10)]);
+        assert_eq!(
+            "High confidence: B is the best",
+            high.confidence(0.05, 2.0, 10.0, &letters())
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            ConfidenceLevel::Medium,
+            level(&with_count(&[(1000, 50), (900, 50)]))
+        );
+        assert_eq!(
+            ConfidenceLevel::Medium,
+            level(&with_count(&[(1000, 10), (1000, 10)]))
+        );
+        assert_eq!(
+            ConfidenceLevel::Low,
+            level(&with_count(&[(1000, 200), (500, 10)]))
+        );
+        let neutral = measure_summary(Direction::Neutral, &[(1000, 10), (900, 10)]);
+        assert_eq!(None, neutral.confidence(0.05, 2.0, 10.0, &letters()));
+    }
+
+    #[test]
+    fn cv() {
+        let summary = measure_summary(Direction::Lower, &[(1000, 10), (0, 0), (500, 25)]);
+        let cv = summary.cv_percent();
+        assert_eq!(Some(1.0), cv[ExperimentName::A]);
+        assert_eq!(None, cv[ExperimentName::B]);
+        assert_eq!(Some(5.0), cv[ExperimentName::C]);
+        assert_eq!(5.0, summary.max_cv_percent());
+    }
+
+    #[test]
+    fn reference() {
+        let stats = measure_summary(Direction::Lower, &[(1000, 10), (900, 10), (500, 10)]).stats;
+        let summary = MeasureSummary::new(
+            MeasureKey::WallTime,
+            "Time",
+            Direction::Lower,
+            stats.map(|s| s.clone()),
+            ExperimentName::C,
+        );
+        assert_eq!(ExperimentName::C, summary.reference);
+        let pairs: Vec<_> = summary
+            .comparisons
+            .iter()
+            .map(|c| (c.other, c.base))
+            .collect();
+        assert_eq!(
+            vec![
+                (ExperimentName::A, ExperimentName::C),
+                (ExperimentName::B, ExperimentName::C)
+            ],
+            pairs
+        );
+        assert!((summary.comparisons[0].ratio - 2.0).abs() < 1e-9);
+
+        // Falls back to the first experiment.
+        let summary = MeasureSummary::new(
+            MeasureKey::WallTime,
+            "Time",
+            Direction::Lower,
+            stats.map(|s| s.clone()),
+            ExperimentName::D,
+        );
+        assert_eq!(ExperimentName::A, summary.reference);
+    }
+
+    #[test]
+    fn comparison() {
+        let mut tests = ExperimentMap::default();
+        for (name, numbers) in [
+            (ExperimentName::A, [100, 102, 98, 101, 99]),
+            (ExperimentName::B, [50, 51, 49, 50, 50]),
+        ]
+        .iter()
+        {
+            let mut test = Experiment::new(*name, String::new(), String::new());
+            for &n in numbers {
+                test.measures[MeasureKey::WallTime].push(n);
+            }
+            tests.insert(*name, test);
+        }
+        let measures = AllMeasures(vec![Box::new(WallTime::new(None, None))]);
+        let report = SummaryReport::new(&measures, &tests, ExperimentName::A);
+        let summary = report.measure(MeasureKey::WallTime).unwrap();
+        assert_eq!(100, summary.stats[ExperimentName::A].mean);
+        assert_eq!(1, summary.comparisons.len());
+        let comparison = &summary.comparisons[0];
+        assert_eq!(ExperimentName::B, comparison.other);
+        assert!((comparison.ratio - 0.5).abs() < 1e-9);
+        assert!(comparison.ratio_min < 0.5 && comparison.ratio_max > 0.5);
+        assert!(comparison.p_value < 0.001);
+        assert!(comparison.is_better(Direction::Lower));
+        assert!(report.measure(MeasureKey::MaxRss).is_none());
+    }
+
+    #[test]
+    fn measure_skipped_by_some() {
+        let mut tests = ExperimentMap::default();
+        for &name in &[ExperimentName::A, ExperimentName::B, ExperimentName::C] {
+            let mut test = Experiment::new(name, String::new(), String::new());
+            for &n in &[10, 11, 9] {
+                test.measures[MeasureKey::WallTime].push(n);
+                if name != ExperimentName::A {
+                    test.measures[MeasureKey::MaxRss].push(n * 1000);
+                }
+            }
+            test.skipped[MeasureKey::MaxRss] = name == ExperimentName::A;
+            tests.insert(name, test);
+        }
+        let measures = AllMeasures(vec![
+            Box::new(WallTime::new(None, None)),
+            Box::new(MaxRss::new(false)),
+        ]);
+        let report = SummaryReport::new(&measures, &tests, ExperimentName::A);
+        let summary = report.measure(MeasureKey::MaxRss).unwrap();
+        assert!(summary.stats.get(ExperimentName::A).is_none());
+        assert_eq!(1, summary.comparisons.len());
+        assert_eq!(ExperimentName::B, summary.comparisons[0].base);
+        assert_eq!(ExperimentName::C, summary.comparisons[0].other);
+    }
+}
@@ -0,0 +1,171 @@
+//! Minimal SVG rendering of histograms.
+
+use std::fmt::Write;
+
+const WIDTH: f64 = 600.0;
+const PLOT_HEIGHT: f64 = 120.0;
+const LABEL_HEIGHT: f64 = 20.0;
+const TITLE_HEIGHT: f64 = 20.0;
+
+/// Escape text for XML and HTML.
+pub fn escape(s: &str) -> String {
+    let mut r = String::new();
+    for c in s.chars() {
+        match c {
+            '<' => r.push_str("&lt;"),
+            '>' => r.push_str("&gt;"),
+            '&' => r.push_str("&amp;"),
+            '"' => r.push_str("&quot;"),
+            '\'' => r.push_str("&#39;"),
+            c => r.push(c),
+        }
+    }
+    r
+}
+
+/// One histogram per experiment, drawn on top of each other.
+pub struct Histogram<'a> {
+    /// CSS color and bucket counts. All series must have the same number of buckets.
+    pub series: Vec<(&'a str, &'a [u64])>,
+    /// Label under the left edge.
+    pub min_label: String,
+    /// Label under the right edge.
+    pub max_label: String,
+}
+
+impl<'a> Histogram<'a> {
+    pub fn render(&self) -> String {
+        let mut r = String::new();
+        let height = PLOT_HEIGHT + LABEL_HEIGHT;
+        writeln!(
+            r,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+            w = WIDTH,
+            h = height,
+        )
+        .unwrap();
+        let max_count = self
+            .series
+            .iter()
+            .flat_map(|(_, counts)| counts.iter())
+            .max()
+            .cloned()
+            .unwrap_or(0);
+        for (color, counts) in &self.series {
+            if counts.is_empty() || max_count == 0 {
+                continue;
+            }
+            let bar_width = WIDTH / counts.len() as f64;
+            for (i, &count) in counts.iter().enumerate() {
+                if count == 0 {
+                    continue;
+                }
+                let bar_height = count as f64 / max_count as f64 * PLOT_HEIGHT;
+                writeln!(
+                    r,
+                    "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\" fill-opacity=\"0.5\"/>",
+                    i as f64 * bar_width,
+                    PLOT_HEIGHT - bar_height,
+                    bar_width,
+                    bar_height,
+                    color,
+                )
+                .unwrap();
+            }
+        }
+        writeln!(
+            r,
+            "<line x1=\"0\" y1=\"{y}\" x2=\"{w}\" y2=\"{y}\" stroke=\"black\"/>",
+            y = PLOT_HEIGHT,
+            w = WIDTH,
+        )
+        .unwrap();
+        writeln!(
+            r,
+            "<text x=\"0\" y=\"{}\" font-size=\"12\" font-family=\"monospace\">{}</text>",
+            height - 4.0,
+            escape(&self.min_label),
+        )
+        .unwrap();
+        writeln!(
+            r,
+            "<text x=\"{}\" y=\"{}\" font-size=\"12\" font-family=\"monospace\" text-anchor=\"end\">{}</text>",
+            WIDTH,
+            height - 4.0,
+            escape(&self.max_label),
+        )
+        .unwrap();
+        writeln!(r, "</svg>").unwrap();
+        r
+    }
+}
+
+/// Titled `Histogram::render` outputs one under another in a single image.
+pub fn stack(sections: &[(String, String)]) -> String {
+    let mut r = String::new();
+    let section_height = TITLE_HEIGHT + PLOT_HEIGHT + LABEL_HEIGHT;
+    writeln!(
+        r,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = WIDTH,
+        h = section_height * sections.len() as f64,
+    )
+    .unwrap();
+    for (i, (title, svg)) in sections.iter().enumerate() {
+        let y = section_height * i as f64;
+        writeln!(
+            r,
+            "<text x=\"0\" y=\"{}\" font-size=\"14\" font-family=\"sans-serif\">{}</text>",
+            y + TITLE_HEIGHT - 4.0,
+            escape(title),
+        )
+        .unwrap();
+        writeln!(r, "<g transform=\"translate(0 {})\">", y + TITLE_HEIGHT).unwrap();
+        r.push_str(svg);
+        writeln!(r, "</g>").unwrap();
+    }
+    writeln!(r, "</svg>").unwrap();
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use crate::svg::Histogram;
+    use crate::svg::escape;
+    use crate::svg::stack;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("a &lt;b&gt; &amp; &quot;c&quot;", escape("a <b> & \"c\""));
+    }
+
+    #[test]
+    fn test_histogram() {
+        let svg = Histogram {
+            series: vec![("red", &[0, 2, 1])],
+            min_label: "1".to_owned(),
+            max_label: "<3".to_owned(),
+        }
+        .render();
+        assert!(svg.starts_with("<svg "));
+        assert_eq!(2, svg.matches("<rect ").count());
+        assert!(svg.contains("&lt;3"));
+    }
+
+    #[test]
+    fn test_stack() {
+        let histogram = Histogram {
+            series: vec![("red", &[1])],
+            min_label: "1".to_owned(),
+            max_label: "2".to_owned(),
+        }
+        .render();
+        let svg = stack(&[
+            ("wall time".to_owned(), histogram.clone()),
+            ("max rss".to_owned(), histogram),
+        ]);
+        assert!(svg.contains("height=\"320\""));
+        assert_eq!(3, svg.matches("<svg ").count());
+        assert!(svg.contains("translate(0 180)"));
+    }
+}
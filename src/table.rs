@@ -0,0 +1,118 @@
+//! Column-aligned text tables.
+
+use std::fmt::Write;
+
+use crate::ansi;
+
+/// Displayed width of a cell, ignoring color escapes.
+fn cell_width(cell: &str) -> usize {
+    ansi::strip_csi(cell).chars().count()
+}
+
+/// Maximum displayed width of each column.
+pub fn column_widths(rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths = Vec::new();
+    for row in rows {
+        if widths.len() < row.len() {
+            widths.resize(row.len(), 0);
+        }
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = usize::max(*width, cell_width(cell));
+        }
+    }
+    widths
+}
+
+/// Right-align `cell` in `width` columns; `format!` padding miscounts color escapes.
+pub fn pad_left(cell: &str, width: usize) -> String {
+    let pad = width.saturating_sub(cell_width(cell));
+    format!("{}{}", " ".repeat(pad), cell)
+}
+
+/// Left-align `cell` in `width` columns.
+pub fn pad_right(cell: &str, width: usize) -> String {
+    let pad = width.saturating_sub(cell_width(cell));
+    format!("{}{}", cell, " ".repeat(pad))
+}
+
+fn border(r: &mut String, widths: &[usize], left: char, middle: char, right: char) {
+    r.push(left);
+    for (i, &width) in widths.iter().enumerate() {
+        if i != 0 {
+            r.push(middle);
+        }
+        r.push_str(&"─".repeat(width + 2));
+    }
+    r.push(right);
+    r.push('\n');
+}
+
+fn row(r: &mut String, widths: &[usize], cells: &[String]) {
+    r.push('│');
+    for (i, &width) in widths.iter().enumerate() {
+        let cell = cells.get(i).map(|c| c.as_str()).unwrap_or("");
+        // First column holds row labels.
+        let cell = if i == 0 {
+            pad_right(cell, width)
+        } else {
+            pad_left(cell, width)
+        };
+        write!(r, " {} │", cell).unwrap();
+    }
+    r.push('\n');
+}
+
+/// Render header and rows with box-drawing borders.
+pub fn render_box(header: &[String], rows: &[Vec<String>]) -> String {
+    let mut all = vec![header.to_vec()];
+    all.extend(rows.iter().cloned());
+    let widths = column_widths(&all);
+
+    let mut r = String::new();
+    border(&mut r, &widths, '┌', '┬', '┐');
+    row(&mut r, &widths, header);
+    border(&mut r, &widths, '├', '┼', '┤');
+    for cells in rows {
+        row(&mut r, &widths, cells);
+    }
+    border(&mut r, &widths, '└', '┴', '┘');
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use crate::table::column_widths;
+    use crate::table::render_box;
+
+    fn strings(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|&c| c.to_owned()).collect()
+    }
+
+    #[test]
+    fn widths_ignore_colors() {
+        let rows = vec![
+            strings(&["\x1b[31mA\x1b[0m", "1.5ms"]),
+            strings(&["BB", "10ms"]),
+        ];
+        assert_eq!(vec![2, 5], column_widths(&rows));
+    }
+
+    #[test]
+    fn box_table() {
+        let table = render_box(
+            &strings(&["", "mean"]),
+            &[strings(&["A", "1.5ms"]), strings(&["B", "10ms"])],
+        );
+        assert_eq!(
+            "\
+┌───┬───────┐
+│   │  mean │
+├───┼───────┤
+│ A │ 1.5ms │
+│ B │  10ms │
+└───┴───────┘
+",
+            table
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! [TAP](https://testanything.org/) output: one test per experiment compared to the reference.
+
+use std::fmt::Write;
+
+use crate::summary::SummaryReport;
+
+/// Experiment fails if any measure is worse than the reference with 95% confidence.
+pub fn tap_report(summary: &SummaryReport) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r, "TAP version 13")?;
+
+    let first = match summary.measures.first() {
+        Some(first) => first,
+        None => {
+            writeln!(r, "1..0 # SKIP not enough samples")?;
+            return Ok(r);
+        }
+    };
+    if first.comparisons.is_empty() {
+        writeln!(
+            r,
+            "1..0 # SKIP nothing to compare {} with",
+            summary.label(first.reference)
+        )?;
+        return Ok(r);
+    }
+
+    writeln!(r, "1..{}", first.comparisons.len())?;
+    for (i, c) in first.comparisons.iter().enumerate() {
+        let mut ok = true;
+        let mut diagnostics = String::new();
+        for measure in &summary.measures {
+            // Measure may be recorded only for some experiments.
+            let comparison = match measure
+                .comparisons
+                .iter()
+                .find(|m| m.base == c.base && m.other == c.other)
+            {
+                Some(comparison) => comparison,
+                None => continue,
+            };
+            let worse = comparison.is_worse(measure.direction);
+            if worse {
+                ok = false;
+            }
+            writeln!(
+                diagnostics,
+                "# {}: {}/{} {:.3} {:.3}..{:.3} (95% conf), p={:.3}{}",
+                measure.name,
+                summary.label(comparison.other),
+                summary.label(comparison.base),
+                comparison.ratio,
+                comparison.ratio_min,
+                comparison.ratio_max,
+                comparison.p_value,
+                if worse { " worse" } else { "" },
+            )?;
+        }
+        writeln!(
+            r,
+            "{} {} - {} within threshold of {}",
+            if ok { "ok" } else { "not ok" },
+            i + 1,
+            summary.label(c.other),
+            summary.label(c.base),
+        )?;
+        r.push_str(&diagnostics);
+    }
+    Ok(r)
+}
@@ -0,0 +1,305 @@
+//! Absolute thresholds checked when the benchmark finishes, e.g. `time.p99=200ms`,
+//! and limits on regressions relative to the reference experiment, e.g. `max-rss:10%`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::duration::Duration;
+use crate::math::numbers::Numbers;
+use crate::measure::direction::Direction;
+use crate::measure::key::MeasureKey;
+
+/// Statistic compared with the target value.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TargetStat {
+    Mean,
+    Med,
+    Min,
+    Max,
+    /// Nearest-rank percentile.
+    Percentile(f64),
+}
+
+impl TargetStat {
+    fn compute(&self, numbers: &Numbers) -> Option<u64> {
+        match self {
+            TargetStat::Mean => numbers.mean(),
+            TargetStat::Med => numbers.med(),
+            TargetStat::Min => numbers.min(),
+            TargetStat::Max => numbers.max(),
+            TargetStat::Percentile(p) => numbers.sorted().percentile(*p),
+        }
+    }
+}
+
+impl FromStr for TargetStat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<TargetStat> {
+        match s {
+            "mean" => Ok(TargetStat::Mean),
+            "med" | "median" => Ok(TargetStat::Med),
+            "min" => Ok(TargetStat::Min),
+            "max" => Ok(TargetStat::Max),
+            _ => {
+                let p: f64 = s
+                    .strip_prefix('p')
+                    .and_then(|p| p.parse().ok())
+                    .filter(|p| (0.0..=100.0).contains(p))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "unknown statistic {:?}, expecting mean, med, min, max or pNN",
+                            s
+                        )
+                    })?;
+                Ok(TargetStat::Percentile(p))
+            }
+        }
+    }
+}
+
+impl fmt::Display for TargetStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetStat::Mean => write!(f, "mean"),
+            TargetStat::Med => write!(f, "med"),
+            TargetStat::Min => write!(f, "min"),
+            TargetStat::Max => write!(f, "max"),
+            TargetStat::Percentile(p) => write!(f, "p{}", p),
+        }
+    }
+}
+
+/// Threshold for a statistic of a measure, like `time.p99=200ms`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Target {
+    pub key: MeasureKey,
+    pub stat: TargetStat,
+    pub value: u64,
+}
+
+/// Bytes, with optional `KiB`, `MiB` or `GiB` suffix.
+fn parse_bytes(s: &str) -> anyhow::Result<u64> {
+    let (number, shift) = if let Some(n) = s.strip_suffix("KiB") {
+        (n, 10)
+    } else if let Some(n) = s.strip_suffix("MiB") {
+        (n, 20)
+    } else if let Some(n) = s.strip_suffix("GiB") {
+        (n, 30)
+    } else {
+        (s, 0)
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid size: {:?}", s))?;
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| anyhow::anyhow!("size too large: {:?}", s))
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    /// Parse `<MEASURE>[.<STAT>]=<VALUE>`, statistic is mean by default.
+    fn from_str(s: &str) -> anyhow::Result<Target> {
+        let (lhs, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expecting MEASURE[.STAT]=VALUE: {:?}", s))?;
+        let (measure, stat) = match lhs.split_once('.') {
+            Some((measure, stat)) => (measure, stat.parse()?),
+            None => (lhs, TargetStat::Mean),
+        };
+        let key: MeasureKey = measure.parse()?;
+        let value = match key {
+            MeasureKey::WallTime => value.parse::<Duration>()?.nanos(),
+            MeasureKey::MaxRss => parse_bytes(value)?,
+            MeasureKey::UserDefinedMetric => value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid metric value: {:?}", value))?,
+            // Units per second, stored in thousandths.
+            MeasureKey::Throughput => match value.parse::<f64>() {
+                Ok(v) if v >= 0.0 && v.is_finite() => (v * 1000.0).round() as u64,
+                _ => return Err(anyhow::anyhow!("invalid throughput: {:?}", value)),
+            },
+        };
+        Ok(Target { key, stat, value })
+    }
+}
+
+impl Target {
+    /// Statistic and whether it meets the target, `None` if there are no samples.
+    ///
+    /// Value must be at most the target, or at least if higher is better.
+    pub fn check(&self, numbers: &Numbers, direction: Direction) -> Option<(u64, bool)> {
+        let actual = self.stat.compute(numbers)?;
+        let pass = match direction {
+            Direction::Higher => actual >= self.value,
+            Direction::Lower | Direction::Neutral => actual <= self.value,
+        };
+        Some((actual, pass))
+    }
+}
+
+/// Largest allowed regression of a measure relative to the reference experiment, like
+/// `max-rss:10%`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorseLimit {
+    pub key: MeasureKey,
+    pub percent: f64,
+}
+
+impl FromStr for WorseLimit {
+    type Err = anyhow::Error;
+
+    /// Parse `<MEASURE>:<PERCENT>[%]`.
+    fn from_str(s: &str) -> anyhow::Result<WorseLimit> {
+        let (measure, percent) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("expecting MEASURE:PERCENT: {:?}", s))?;
+        let key: MeasureKey = measure.parse()?;
+        let percent = percent
+            .strip_suffix('%')
+            .unwrap_or(percent)
+            .parse::<f64>()
+            .ok()
+            .filter(|p| *p >= 0.0)
+            .ok_or_else(|| anyhow::anyhow!("invalid percent: {:?}", percent))?;
+        Ok(WorseLimit { key, percent })
+    }
+}
+
+impl WorseLimit {
+    /// Is ratio `b/a` worse than `1` by more than the limit.
+    pub fn exceeded(&self, ratio: f64, direction: Direction) -> bool {
+        match direction {
+            Direction::Lower => ratio > 1.0 + self.percent / 100.0,
+            Direction::Higher => ratio < 1.0 - self.percent / 100.0,
+            Direction::Neutral => false,
+        }
+    }
+}
+
+/// Weights of measures in a composite score, like `wall-time:0.7,max-rss:0.3`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreWeights(pub Vec<(MeasureKey, f64)>);
+
+impl FromStr for ScoreWeights {
+    type Err = anyhow::Error;
+
+    /// Parse comma separated `<MEASURE>:<WEIGHT>`.
+    fn from_str(s: &str) -> anyhow::Result<ScoreWeights> {
+        let mut weights: Vec<(MeasureKey, f64)> = Vec::new();
+        for part in s.split(',') {
+            let (measure, weight) = part
+                .split_once(':')
+                .ok_or_else(|| anyhow::anyhow!("expecting MEASURE:WEIGHT: {:?}", part))?;
+            let key: MeasureKey = measure.parse()?;
+            let weight = weight
+                .parse::<f64>()
+                .ok()
+                .filter(|w| *w > 0.0 && w.is_finite())
+                .ok_or_else(|| anyhow::anyhow!("invalid weight: {:?}", weight))?;
+            if weights.iter().any(|&(k, _)| k == key) {
+                return Err(anyhow::anyhow!("duplicate measure: {:?}", measure));
+            }
+            weights.push((key, weight));
+        }
+        Ok(ScoreWeights(weights))
+    }
+}
+
+impl fmt::Display for ScoreWeights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (key, weight)) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}:{}", key.id(), weight)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::math::numbers::Numbers;
+    use crate::measure::direction::Direction;
+    use crate::measure::key::MeasureKey;
+    use crate::target::ScoreWeights;
+    use crate::target::Target;
+    use crate::target::TargetStat;
+    use crate::target::WorseLimit;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Target {
+                key: MeasureKey::WallTime,
+                stat: TargetStat::Percentile(99.0),
+                value: 200_000_000,
+            },
+            "time.p99=200ms".parse().unwrap()
+        );
+        assert_eq!(
+            Target {
+                key: MeasureKey::MaxRss,
+                stat: TargetStat::Mean,
+                value: 512 << 20,
+            },
+            "rss=512MiB".parse().unwrap()
+        );
+        assert!("time.p101=1s".parse::<Target>().is_err());
+        assert!("foo=1".parse::<Target>().is_err());
+        assert!("time".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn check() {
+        let numbers = Numbers::from_slice(&[10, 20, 30, 40]);
+        let target: Target = "metric.max=35".parse().unwrap();
+        assert_eq!(Some((40, false)), target.check(&numbers, Direction::Lower));
+        assert_eq!(Some((40, true)), target.check(&numbers, Direction::Higher));
+        let target: Target = "metric.med=25".parse().unwrap();
+        assert_eq!(Some((25, true)), target.check(&numbers, Direction::Neutral));
+        assert_eq!(None, target.check(&Numbers::default(), Direction::Lower));
+    }
+
+    #[test]
+    fn worse_limit() {
+        let limit: WorseLimit = "max-rss:10%".parse().unwrap();
+        assert_eq!(
+            WorseLimit {
+                key: MeasureKey::MaxRss,
+                percent: 10.0,
+            },
+            limit
+        );
+        assert_eq!(limit, "rss:10".parse().unwrap());
+        assert!("rss".parse::<WorseLimit>().is_err());
+        assert!("rss:-1%".parse::<WorseLimit>().is_err());
+
+        assert!(limit.exceeded(1.2, Direction::Lower));
+        assert!(!limit.exceeded(1.05, Direction::Lower));
+        assert!(!limit.exceeded(1.2, Direction::Higher));
+        assert!(limit.exceeded(0.8, Direction::Higher));
+    }
+
+    #[test]
+    fn score_weights() {
+        let weights: ScoreWeights = "wall-time:0.7,max-rss:0.3".parse().unwrap();
+        assert_eq!(
+            ScoreWeights(vec![(MeasureKey::WallTime, 0.7), (MeasureKey::MaxRss, 0.3)]),
+            weights
+        );
+        assert_eq!("wall-time:0.7,max-rss:0.3", weights.to_string());
+        assert_eq!(
+            ScoreWeights(vec![(MeasureKey::WallTime, 1.0), (MeasureKey::MaxRss, 2.0)]),
+            "time:1,rss:2".parse().unwrap()
+        );
+        let e = "cpu:1".parse::<ScoreWeights>().unwrap_err().to_string();
+        assert!(e.contains("unknown measure"), "{}", e);
+        assert!("time:1,wall-time:2".parse::<ScoreWeights>().is_err());
+        assert!("wall-time:0".parse::<ScoreWeights>().is_err());
+        assert!("wall-time:1,wall-time:2".parse::<ScoreWeights>().is_err());
+    }
+}
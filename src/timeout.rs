@@ -0,0 +1,158 @@
+use std::fmt;
+use std::process::Child;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::thread::JoinHandle;
+
+use crate::duration::Duration;
+
+/// Signal sent to a process group.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Signal(i32);
+
+#[cfg(unix)]
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("TERM", libc::SIGTERM),
+];
+
+/// POSIX numbers, so `--timeout-signal` parses the same everywhere.
+#[cfg(not(unix))]
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", 1),
+    ("INT", 2),
+    ("QUIT", 3),
+    ("KILL", 9),
+    ("TERM", 15),
+];
+
+impl Signal {
+    pub(crate) fn from_raw(signal: i32) -> Signal {
+        Signal(signal)
+    }
+
+    #[cfg(unix)]
+    pub const KILL: Signal = Signal(libc::SIGKILL);
+    #[cfg(not(unix))]
+    pub const KILL: Signal = Signal(9);
+
+    #[cfg(unix)]
+    pub const TERM: Signal = Signal(libc::SIGTERM);
+    #[cfg(not(unix))]
+    pub const TERM: Signal = Signal(15);
+}
+
+impl FromStr for Signal {
+    type Err = anyhow::Error;
+
+    /// Parse signal like `TERM`, `SIGTERM` or `15`.
+    fn from_str(s: &str) -> anyhow::Result<Signal> {
+        if let Ok(n) = s.parse::<i32>() {
+            // Signal 0 only checks the process exists, so the timeout would not kill it.
+            if n <= 0 {
+                return Err(anyhow::anyhow!("signal must be positive: {}", n));
+            }
+            return Ok(Signal(n));
+        }
+        let name = s.strip_prefix("SIG").unwrap_or(s);
+        SIGNALS
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, n)| Signal(*n))
+            .ok_or_else(|| anyhow::anyhow!("unknown signal: {:?}", s))
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match SIGNALS.iter().find(|(_, n)| *n == self.0) {
+            Some((name, _)) => write!(f, "SIG{}", name),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kill_group(pgid: u32, signal: Signal) {
+    // Process group may be already gone, ignore errors.
+    unsafe {
+        libc::killpg(pgid as libc::pid_t, signal.0);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_group(_pgid: u32, _signal: Signal) {}
+
+/// Kill a process if it runs for too long.
+pub struct Timeout {
+    pub duration: Duration,
+    /// Signal sent first.
+    pub signal: Signal,
+    /// Time to wait after the first signal before sending `SIGKILL`.
+    pub grace: Duration,
+}
+
+/// Thread which kills process group when timeout expires.
+pub struct Watchdog {
+    cancel: mpsc::Sender<()>,
+    thread: JoinHandle<bool>,
+}
+
+impl Watchdog {
+    /// Start watching the process, which must be a process group leader.
+    pub fn start(child: &Child, timeout: &Timeout) -> Watchdog {
+        let (cancel, cancelled) = mpsc::channel();
+        let pgid = child.id();
+        let duration = timeout.duration;
+        let signal = timeout.signal;
+        let grace = timeout.grace;
+        let thread = thread::spawn(move || {
+            let wait = |d: Duration| {
+                matches!(
+                    cancelled.recv_timeout(std::time::Duration::from_nanos(d.nanos())),
+                    Err(RecvTimeoutError::Timeout)
+                )
+            };
+            if !wait(duration) {
+                return false;
+            }
+            kill_group(pgid, signal);
+            if signal != Signal::KILL && wait(grace) {
+                kill_group(pgid, Signal::KILL);
+            }
+            true
+        });
+        Watchdog { cancel, thread }
+    }
+
+    /// Stop watching after the process exited. Return true if the process was killed.
+    pub fn finish(self) -> bool {
+        let _ = self.cancel.send(());
+        self.thread.join().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::timeout::Signal;
+
+    #[test]
+    fn parse_signal() {
+        assert_eq!(Signal::TERM, "TERM".parse().unwrap());
+        assert_eq!(Signal::TERM, "SIGTERM".parse().unwrap());
+        assert_eq!(Signal::TERM, "term".parse().unwrap());
+        assert_eq!(Signal::KILL, "KILL".parse().unwrap());
+        assert_eq!(Signal(9), "9".parse().unwrap());
+        assert_eq!("SIGKILL", Signal::KILL.to_string());
+        assert!("FOO".parse::<Signal>().is_err());
+        assert!("0".parse::<Signal>().is_err());
+        assert!("-9".parse::<Signal>().is_err());
+    }
+}
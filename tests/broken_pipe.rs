@@ -0,0 +1,52 @@
+#![cfg(unix)]
+
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::process::Command;
+use std::process::Output;
+use std::process::Stdio;
+
+/// Pipe whose reader is already closed, so writes to the returned fd fail with `EPIPE`.
+fn closed_pipe() -> File {
+    let mut fds = [0; 2];
+    assert_eq!(0, unsafe { libc::pipe(fds.as_mut_ptr()) });
+    drop(unsafe { File::from_raw_fd(fds[0]) });
+    unsafe { File::from_raw_fd(fds[1]) }
+}
+
+fn absh(home: &str, args: &[&str], stdout: Stdio) -> Output {
+    let home = std::env::temp_dir().join(format!("absh-test-{}-{}", home, std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_absh"))
+        .args(["-a", "true", "-n", "3", "--color", "never"])
+        .args(args)
+        .env("HOME", &home)
+        .stdout(stdout)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+    output
+}
+
+#[test]
+fn progress_fd_closed() {
+    let pipe = closed_pipe();
+    let fd = pipe.as_raw_fd().to_string();
+    let output = absh("progress-fd", &["--progress-fd", &fd], Stdio::null());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{:?}\n{}", output.status, stderr);
+    assert!(stderr.contains("Time:"), "{}", stderr);
+    assert!(
+        stderr.contains("Stopped: reached 3 iterations."),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn stdout_closed() {
+    let output = absh("stdout", &["--tap"], Stdio::from(closed_pipe()));
+    assert_eq!(Some(141), output.status.code(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
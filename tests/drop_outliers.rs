@@ -0,0 +1,30 @@
+#![cfg(unix)]
+
+use std::process::Command;
+
+/// Exit code of `absh -a <script> -n 20` with extra flags, where the 10th run
+/// of the script is an outlier taking 500ms.
+fn run(home: &str, flags: &[&str]) -> Option<i32> {
+    let home = std::env::temp_dir().join(format!("absh-test-{}-{}", home, std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    let script = format!(
+        "n=$(cat {dir}/n 2>/dev/null || echo 0); echo $((n + 1)) > {dir}/n; \
+        if [ $n = 10 ]; then sleep 0.5; fi",
+        dir = home.display()
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_absh"))
+        .args(["-a", &script, "-n", "20", "--color", "never"])
+        .args(["--target", "time.max=300ms"])
+        .args(flags)
+        .env("HOME", &home)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+    output.status.code()
+}
+
+#[test]
+fn target_checked_without_outliers() {
+    assert_eq!(Some(3), run("outlier-kept", &[]));
+    assert_eq!(Some(0), run("outlier-dropped", &["--drop-outliers"]));
+}
@@ -0,0 +1,49 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::process::Command;
+
+/// Stderr of `absh -a true -n 3` with extra flags, and the last run log.
+fn run(home: &str, flags: &[&str]) -> (String, String) {
+    let home = std::env::temp_dir().join(format!("absh-test-{}-{}", home, std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_absh"))
+        .args(["-a", "true", "-n", "3", "--color", "never"])
+        .args(flags)
+        .env("HOME", &home)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let log = fs::read_to_string(home.join(".absh/logs/last/log")).unwrap();
+    fs::remove_dir_all(&home).unwrap();
+    (String::from_utf8(output.stderr).unwrap(), log)
+}
+
+#[test]
+fn verbosity_levels() {
+    let (quiet, log) = run("quiet", &["-q"]);
+    assert!(quiet.contains("Time:"), "{}", quiet);
+    assert!(
+        quiet.contains("Stopped: reached 3 iterations."),
+        "{}",
+        quiet
+    );
+    assert!(!quiet.contains("running test: A"), "{}", quiet);
+    assert!(!quiet.contains("Writing absh data"), "{}", quiet);
+    // Progress is still logged.
+    assert!(log.contains("verbosity: quiet"), "{}", log);
+    assert!(log.contains("running test: A"), "{}", log);
+
+    let (normal, log) = run("normal", &[]);
+    assert!(normal.contains("running test: A"), "{}", normal);
+    assert!(!normal.contains("command: "), "{}", normal);
+    assert!(log.contains("verbosity: normal"), "{}", log);
+
+    let (verbose, _) = run("verbose", &["-v"]);
+    assert!(verbose.contains("cwd: "), "{}", verbose);
+    assert!(verbose.contains("command: "), "{}", verbose);
+    assert!(!verbose.contains("A sample: "), "{}", verbose);
+
+    let (debug, log) = run("debug", &["-vv"]);
+    assert!(debug.contains("A sample: wall-time="), "{}", debug);
+    assert!(log.contains("A sample: wall-time="), "{}", log);
+}